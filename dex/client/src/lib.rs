@@ -0,0 +1,88 @@
+//! Builds the `SwapIntent` a user commits to before `reveal_and_swap`. The intent type and its
+//! hashing come straight from the program crate, so the commitment computed here is the one the
+//! program recomputes at reveal time.
+use anchor_lang::prelude::{Pubkey, Result};
+use jupiter_cpi::RoutePlanStep;
+use rand::RngCore;
+
+pub use dex::intent::{min_out, pool_route_hash, route_hash};
+pub use dex::{SwapIntent, SwapRoute, SWAP_INTENT_VERSION};
+
+/// A swap as quoted by Jupiter, before it is committed.
+pub struct SwapQuote {
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    pub amount_in: u64,
+    pub quoted_out_amount: u64,
+    pub slippage_bps: u16,
+    pub route_plan: Vec<RoutePlanStep>,
+}
+
+/// A random salt, so the committed intent can't be guessed from its hash.
+pub fn new_salt() -> [u8; 32] {
+    let mut salt = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// The intent to commit for `quote`. `nonce` has to be above the session's current nonce and
+/// `expiry_slot` in the future; reveal it with `jupiter_route(quote, ..)`.
+pub fn intent_for_quote(user: Pubkey, quote: &SwapQuote, nonce: u64, expiry_slot: u64, salt: [u8; 32]) -> Result<SwapIntent> {
+    Ok(SwapIntent {
+        version: SWAP_INTENT_VERSION,
+        user,
+        token_in: quote.token_in,
+        token_out: quote.token_out,
+        amount_in: quote.amount_in,
+        min_out: min_out(quote.quoted_out_amount, quote.slippage_bps),
+        slippage_bps: quote.slippage_bps,
+        route_hash: route_hash(&quote.route_plan)?,
+        nonce,
+        expiry_slot,
+        salt,
+    })
+}
+
+/// The intent to commit for a swap through one of the program's own pools, revealed with
+/// `SwapRoute::Pool`.
+#[allow(clippy::too_many_arguments)]
+pub fn intent_for_pool_swap(
+    user: Pubkey,
+    pool: Pubkey,
+    token_in: Pubkey,
+    token_out: Pubkey,
+    amount_in: u64,
+    min_out: u64,
+    nonce: u64,
+    expiry_slot: u64,
+    salt: [u8; 32],
+) -> SwapIntent {
+    SwapIntent {
+        version: SWAP_INTENT_VERSION,
+        user,
+        token_in,
+        token_out,
+        amount_in,
+        min_out,
+        slippage_bps: 0,
+        route_hash: pool_route_hash(&pool),
+        nonce,
+        expiry_slot,
+        salt,
+    }
+}
+
+/// The `SwapRoute` to reveal `quote` with.
+pub fn jupiter_route(quote: &SwapQuote, id: u64, platform_fee_bps: u8) -> SwapRoute {
+    SwapRoute::Jupiter {
+        id,
+        route_plan: quote.route_plan.clone(),
+        quoted_out_amount: quote.quoted_out_amount,
+        platform_fee_bps,
+    }
+}
+
+/// The commitment to pass to `commit_swap`.
+pub fn commitment(intent: &SwapIntent) -> Result<[u8; 32]> {
+    intent.commitment()
+}
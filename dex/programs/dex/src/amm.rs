@@ -0,0 +1,149 @@
+// Constant-product (x * y = k) math for the native pools. Every result rounds in the pool's
+// favour, so k never goes down.
+
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+// output for `amount_in` after the fee, which stays in the pool for LPs
+pub fn swap_out(amount_in: u64, reserve_in: u64, reserve_out: u64, fee_bps: u16) -> Option<u64> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return None;
+    }
+    let in_after_fee = amount_in as u128 * (10_000 - fee_bps as u128);
+    // only overflows for inputs and reserves both close to u64::MAX
+    let out = in_after_fee.checked_mul(reserve_out as u128)? / (reserve_in as u128 * 10_000 + in_after_fee);
+    u64::try_from(out).ok()
+}
+
+// LP tokens minted for a deposit: the geometric mean for the first one, then the smaller of the
+// two pro-rata shares. Only deposit_for_lp() of each side is taken for the later ones.
+pub fn lp_for_deposit(amount_a: u64, amount_b: u64, reserve_a: u64, reserve_b: u64, supply: u64) -> Option<u64> {
+    let lp = if supply == 0 {
+        isqrt(amount_a as u128 * amount_b as u128)
+    } else {
+        if reserve_a == 0 || reserve_b == 0 {
+            return None;
+        }
+        let by_a = amount_a as u128 * supply as u128 / reserve_a as u128;
+        let by_b = amount_b as u128 * supply as u128 / reserve_b as u128;
+        by_a.min(by_b)
+    };
+    u64::try_from(lp).ok()
+}
+
+// what minting `lp` more tokens costs out of one reserve, rounded up
+pub fn deposit_for_lp(lp: u64, reserve: u64, supply: u64) -> u64 {
+    let product = lp as u128 * reserve as u128;
+    let amount = product / supply as u128;
+    if amount * (supply as u128) < product {
+        return amount as u64 + 1;
+    }
+    amount as u64
+}
+
+// the reserves `lp` out of `supply` LP tokens redeem for
+pub fn withdraw_amounts(lp: u64, reserve_a: u64, reserve_b: u64, supply: u64) -> (u64, u64) {
+    let share = |reserve: u64| (lp as u128 * reserve as u128 / supply as u128) as u64;
+    (share(reserve_a), share(reserve_b))
+}
+
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = (x >> 1) + (x & 1); // x / 2 rounded up
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_out_rounds_down() {
+        // 1_000_000 less 0.3% against 100_000_000 / 100_000_000 reserves is 987_158.9..
+        assert_eq!(swap_out(1_000_000, 100_000_000, 100_000_000, 30), Some(987_158));
+        // 1 * 3 / (3 + 1) is 0.75, so a dust swap gets nothing
+        assert_eq!(swap_out(1, 3, 3, 0), Some(0));
+        assert_eq!(swap_out(1, 1, 2, 0), Some(1));
+    }
+
+    #[test]
+    fn k_never_goes_down() {
+        let reserves = [(1, 1), (3, 7), (1_000, 1_000_000), (100_000_000, 100_000_000), (u64::MAX / 2, 12_345)];
+        for (reserve_in, reserve_out) in reserves {
+            for amount_in in [1, 2, 999, 1_000_000, u32::MAX as u64] {
+                for fee_bps in [0, 30, MAX_FEE_BPS] {
+                    let out = swap_out(amount_in, reserve_in, reserve_out, fee_bps).unwrap();
+                    assert!(out < reserve_out);
+                    let k_before = reserve_in as u128 * reserve_out as u128;
+                    let k_after = (reserve_in as u128 + amount_in as u128) * (reserve_out - out) as u128;
+                    assert!(k_after >= k_before, "{} in against {}/{} at {} bps", amount_in, reserve_in, reserve_out, fee_bps);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn first_deposit_mints_the_geometric_mean() {
+        assert_eq!(lp_for_deposit(100_000_000, 100_000_000, 0, 0, 0), Some(100_000_000));
+        assert_eq!(lp_for_deposit(4, 9, 0, 0, 0), Some(6));
+        // rounded down when the product isn't a square
+        assert_eq!(lp_for_deposit(2, 4, 0, 0, 0), Some(2));
+        assert_eq!(lp_for_deposit(1, 0, 0, 0, 0), Some(0));
+        assert_eq!(lp_for_deposit(u64::MAX, u64::MAX, 0, 0, 0), Some(u64::MAX));
+        assert_eq!(isqrt(u64::MAX as u128 * u64::MAX as u128 - 1), u64::MAX as u128 - 1);
+    }
+
+    #[test]
+    fn empty_reserves_and_overflows_are_rejected() {
+        assert_eq!(swap_out(1_000, 0, 1_000, 30), None);
+        assert_eq!(swap_out(1_000, 1_000, 0, 30), None);
+        assert_eq!(swap_out(0, 1_000, 1_000, 30), Some(0));
+        // large swaps against large reserves still fit the u128 intermediates
+        assert_eq!(swap_out(u64::MAX, u64::MAX, 1 << 40, 0), Some(1 << 39));
+        // until both are near u64::MAX
+        assert_eq!(swap_out(u64::MAX, 1, u64::MAX, 0), None);
+
+        assert_eq!(lp_for_deposit(1_000, 1_000, 0, 1_000, 1_000), None);
+        assert_eq!(lp_for_deposit(1_000, 1_000, 1_000, 0, 1_000), None);
+        // more LP than a u64 can hold
+        assert_eq!(lp_for_deposit(u64::MAX, u64::MAX, 1, 1, u64::MAX), None);
+    }
+
+    #[test]
+    fn later_deposits_take_the_smaller_share_and_round_up() {
+        // twice as much b as the pool's ratio asks for only earns a's share
+        assert_eq!(lp_for_deposit(10, 40, 100, 200, 1_000), Some(100));
+        assert_eq!(deposit_for_lp(100, 100, 1_000), 10);
+        assert_eq!(deposit_for_lp(100, 200, 1_000), 20);
+        // 1 * 3 / 2 is 1.5
+        assert_eq!(deposit_for_lp(1, 3, 2), 2);
+
+        // what deposit_for_lp charges always earns the LP it was asked for, and never dilutes
+        // the tokens already out
+        let (reserve_a, reserve_b, supply) = (1_000_003u64, 7_777_777u64, 2_718_281u64);
+        for (amount_a, amount_b) in [(1, 1), (12_345, 99_999), (1_000_003, 1), (500_000, 3_888_888)] {
+            let lp = lp_for_deposit(amount_a, amount_b, reserve_a, reserve_b, supply).unwrap();
+            let deposit_a = deposit_for_lp(lp, reserve_a, supply);
+            let deposit_b = deposit_for_lp(lp, reserve_b, supply);
+            assert!(deposit_a <= amount_a && deposit_b <= amount_b);
+            assert!(lp_for_deposit(deposit_a, deposit_b, reserve_a, reserve_b, supply).unwrap() >= lp);
+            for (reserve, deposit) in [(reserve_a, deposit_a), (reserve_b, deposit_b)] {
+                let per_lp_before = reserve as u128 * (supply + lp) as u128;
+                let per_lp_after = (reserve + deposit) as u128 * supply as u128;
+                assert!(per_lp_after >= per_lp_before);
+            }
+        }
+    }
+
+    #[test]
+    fn withdrawals_round_down() {
+        assert_eq!(withdraw_amounts(1, 3, 5, 2), (1, 2));
+        assert_eq!(withdraw_amounts(2, 3, 5, 2), (3, 5));
+    }
+}
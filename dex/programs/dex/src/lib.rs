@@ -1,40 +1,54 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::keccak;
-use anchor_spl::token::{Token, TokenAccount, Transfer};
+use anchor_spl::token::{Burn, Mint, MintTo, Token, TokenAccount, Transfer};
 use jupiter_cpi::cpi::accounts::SharedAccountsRoute;
 use jupiter_cpi::cpi::shared_accounts_route;
 use jupiter_cpi::ID as JUPITER_PROGRAM_ID;
 
+pub mod intent;
+pub use intent::{SwapIntent, SwapRoute, SWAP_INTENT_VERSION};
+pub mod amm;
+
 declare_id!("76j3Mhhr64JU2Lj1FMV1dPErgmJMVgpPcm19nyx1XHDF");
 
 #[program]
 pub mod dex {
     use super::*;
 
-    pub fn commit_swap(ctx: Context<CommitSwap>, commitment: [u8; 32]) -> Result<()> {
+    pub fn init_session(ctx: Context<InitSession>) -> Result<()> {
         let session = &mut ctx.accounts.session;
         session.user = ctx.accounts.user.key();
-        session.commitment = commitment;
+        session.commitment = [0u8; 32];
         session.revealed = false;
         session.bump = ctx.bumps.session;
+        session.nonce = 0;
+        session.expiry_slot = 0;
+
+        msg!("Swap session opened");
+        Ok(())
+    }
+
+    // `commitment` is SwapIntent::commitment() of the swap to be revealed. Every commitment carries
+    // a fresh nonce, strictly above the last one, and the last slot it can be revealed in; both are
+    // part of the intent, so an old reveal can't be replayed.
+    pub fn commit_swap(ctx: Context<CommitSwap>, commitment: [u8; 32], nonce: u64, expiry_slot: u64) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+        require!(session.user == ctx.accounts.user.key(), CustomError::Unauthorized);
+        require!(nonce > session.nonce, CustomError::NonceNotIncreasing);
+        require!(expiry_slot > Clock::get()?.slot, CustomError::InvalidExpiry);
 
-        msg!("Swap committed");
+        session.commitment = commitment;
+        session.revealed = false;
+        session.nonce = nonce;
+        session.expiry_slot = expiry_slot;
+
+        msg!("Swap committed with nonce {}", nonce);
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn reveal_and_swap(
-        ctx: Context<RevealSwap>,
-        salt: [u8; 32],
-        token_in: Pubkey,
-        token_out: Pubkey,
-        amount: u64,
-        id: u64,
-        route_plan: Vec<jupiter_cpi::RoutePlanStep>,
-        quoted_out_amount: u64,
-        slippage_bps: u16,
-        platform_fee_bps: u8,
-    ) -> Result<()> {
+    // the reveal can only execute the committed intent: same mints and amount, the committed
+    // route and slippage, and at least `min_out` has to arrive in the destination account. The
+    // route goes either through Jupiter or through one of the program's own pools.
+    pub fn reveal_and_swap(ctx: Context<RevealSwap>, intent: SwapIntent, route: SwapRoute) -> Result<()> {
         let user_key = ctx.accounts.session.user;
         let session_bump = ctx.accounts.session.bump;
         let commitment = ctx.accounts.session.commitment;
@@ -42,50 +56,236 @@ pub mod dex {
 
         require!(user_key == ctx.accounts.user.key(), CustomError::Unauthorized);
         require!(!is_revealed, CustomError::AlreadyRevealed);
+        require!(intent.version == SWAP_INTENT_VERSION, CustomError::UnsupportedIntentVersion);
+        require!(intent.user == user_key, CustomError::Unauthorized);
+        require!(intent.nonce == ctx.accounts.session.nonce, CustomError::StaleNonce);
+        require!(intent.expiry_slot == ctx.accounts.session.expiry_slot, CustomError::CommitmentMismatch);
+        require!(Clock::get()?.slot <= intent.expiry_slot, CustomError::CommitmentExpired);
+        require!(intent.commitment()? == commitment, CustomError::CommitmentMismatch);
 
-        let mut buf = token_in.as_ref().to_vec();
-        buf.extend(token_out.as_ref());
-        buf.extend(&amount.to_le_bytes());
-        buf.extend(&salt);
-        let hash = keccak::hash(&buf).0;
-        require!(hash == commitment, CustomError::CommitmentMismatch);
+        require!(ctx.accounts.source_mint.key() == intent.token_in, CustomError::MintMismatch);
+        require!(ctx.accounts.destination_mint.key() == intent.token_out, CustomError::MintMismatch);
+        let balance_before = ctx.accounts.destination_token_account.amount;
 
         ctx.accounts.session.revealed = true;
 
-        let signer_seeds: &[&[u8]] = &[b"session", user_key.as_ref(), &[session_bump]];
-        let signers = &[signer_seeds];
+        match route {
+            SwapRoute::Jupiter { id, route_plan, quoted_out_amount, platform_fee_bps } => {
+                require!(intent::route_hash(&route_plan)? == intent.route_hash, CustomError::RouteMismatch);
+                // Jupiter enforces quoted_out_amount less the slippage, which must not undercut min_out
+                require!(
+                    intent::min_out(quoted_out_amount, intent.slippage_bps) >= intent.min_out,
+                    CustomError::SlippageExceeded
+                );
+
+                let accounts = &ctx.accounts;
+                let (
+                    Some(jupiter_program),
+                    Some(user_transfer_authority),
+                    Some(program_source_token_account),
+                    Some(program_destination_token_account),
+                    Some(platform_fee_account),
+                    Some(token_2022_program),
+                ) = (
+                    &accounts.jupiter_program,
+                    &accounts.user_transfer_authority,
+                    &accounts.program_source_token_account,
+                    &accounts.program_destination_token_account,
+                    &accounts.platform_fee_account,
+                    &accounts.token_2022_program,
+                ) else {
+                    return err!(CustomError::MissingRouteAccounts);
+                };
+
+                let signer_seeds: &[&[u8]] = &[b"session", user_key.as_ref(), &[session_bump]];
+                let signers = &[signer_seeds];
+
+                let cpi_accounts = SharedAccountsRoute {
+                    token_program: accounts.token_program.to_account_info(),
+                    program_authority: accounts.session.to_account_info(),
+                    user_transfer_authority: user_transfer_authority.to_account_info(),
+                    source_token_account: accounts.source_token_account.to_account_info(),
+                    program_source_token_account: program_source_token_account.to_account_info(),
+                    program_destination_token_account: program_destination_token_account.to_account_info(),
+                    destination_token_account: accounts.destination_token_account.to_account_info(),
+                    source_mint: accounts.source_mint.to_account_info(),
+                    destination_mint: accounts.destination_mint.to_account_info(),
+                    platform_fee_account: platform_fee_account.to_account_info(),
+                    token_2022_program: token_2022_program.to_account_info(),
+                };
+
+                let cpi_ctx = CpiContext::new_with_signer(
+                    jupiter_program.to_account_info(),
+                    cpi_accounts,
+                    signers,
+                );
+
+                shared_accounts_route(
+                    cpi_ctx,
+                    id,
+                    route_plan,
+                    intent.amount_in,
+                    quoted_out_amount,
+                    intent.slippage_bps,
+                    platform_fee_bps,
+                )?;
+                msg!("Jupiter swap executed successfully");
+            }
+            SwapRoute::Pool => {
+                let accounts = &ctx.accounts;
+                let (Some(pool), Some(pool_vault_in), Some(pool_vault_out)) =
+                    (&accounts.pool, &accounts.pool_vault_in, &accounts.pool_vault_out)
+                else {
+                    return err!(CustomError::MissingRouteAccounts);
+                };
+                require!(intent::pool_route_hash(&pool.key()) == intent.route_hash, CustomError::RouteMismatch);
 
-        let accounts = SharedAccountsRoute {
-            token_program: ctx.accounts.token_program.to_account_info(),
-            program_authority: ctx.accounts.session.to_account_info(),
-            user_transfer_authority: ctx.accounts.user_transfer_authority.to_account_info(),
-            source_token_account: ctx.accounts.source_token_account.to_account_info(),
-            program_source_token_account: ctx.accounts.program_source_token_account.to_account_info(),
-            program_destination_token_account: ctx.accounts.program_destination_token_account.to_account_info(),
-            destination_token_account: ctx.accounts.destination_token_account.to_account_info(),
-            source_mint: ctx.accounts.source_mint.to_account_info(),
-            destination_mint: ctx.accounts.destination_mint.to_account_info(),
-            platform_fee_account: ctx.accounts.platform_fee_account.to_account_info(),
-            token_2022_program: ctx.accounts.token_2022_program.to_account_info(),
+                swap_through_pool(
+                    pool,
+                    pool_vault_in,
+                    pool_vault_out,
+                    &accounts.source_token_account.to_account_info(),
+                    &accounts.destination_token_account.to_account_info(),
+                    &accounts.user.to_account_info(),
+                    &accounts.token_program,
+                    intent.amount_in,
+                    intent.min_out,
+                )?;
+            }
+        }
+
+        ctx.accounts.destination_token_account.reload()?;
+        let received = ctx.accounts.destination_token_account.amount.saturating_sub(balance_before);
+        require!(received >= intent.min_out, CustomError::SlippageExceeded);
+        Ok(())
+    }
+
+    // a native x * y = k pool for a mint pair, stored with mint_a < mint_b so each pair has one pool
+    pub fn create_pool(ctx: Context<CreatePool>, fee_bps: u16) -> Result<()> {
+        require!(ctx.accounts.mint_a.key() < ctx.accounts.mint_b.key(), CustomError::InvalidPoolMints);
+        require!(fee_bps <= amm::MAX_FEE_BPS, CustomError::InvalidFee);
+
+        let pool = &mut ctx.accounts.pool;
+        pool.mint_a = ctx.accounts.mint_a.key();
+        pool.mint_b = ctx.accounts.mint_b.key();
+        pool.vault_a = ctx.accounts.vault_a.key();
+        pool.vault_b = ctx.accounts.vault_b.key();
+        pool.lp_mint = ctx.accounts.lp_mint.key();
+        pool.fee_bps = fee_bps;
+        pool.bump = ctx.bumps.pool;
+
+        msg!("Pool created with a {} bps fee", fee_bps);
+        Ok(())
+    }
+
+    // the first deposit sets the price; later ones are taken at the pool's ratio, so only the
+    // amounts the minted LP tokens are worth leave the user's accounts
+    pub fn add_liquidity(ctx: Context<PoolLiquidity>, amount_a: u64, amount_b: u64, min_lp: u64) -> Result<()> {
+        let reserve_a = ctx.accounts.vault_a.amount;
+        let reserve_b = ctx.accounts.vault_b.amount;
+        let supply = ctx.accounts.lp_mint.supply;
+
+        let lp = amm::lp_for_deposit(amount_a, amount_b, reserve_a, reserve_b, supply).ok_or(CustomError::InsufficientLiquidity)?;
+        require!(lp > 0, CustomError::ZeroAmount);
+        require!(lp >= min_lp, CustomError::SlippageExceeded);
+        let (deposit_a, deposit_b) = if supply == 0 {
+            (amount_a, amount_b)
+        } else {
+            (amm::deposit_for_lp(lp, reserve_a, supply), amm::deposit_for_lp(lp, reserve_b, supply))
         };
 
+        let accounts = &ctx.accounts;
+        for (from, to, amount) in [
+            (&accounts.user_token_a, &accounts.vault_a, deposit_a),
+            (&accounts.user_token_b, &accounts.vault_b, deposit_b),
+        ] {
+            let cpi_ctx = CpiContext::new(
+                accounts.token_program.to_account_info(),
+                Transfer {
+                    from: from.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: accounts.user.to_account_info(),
+                },
+            );
+            anchor_spl::token::transfer(cpi_ctx, amount)?;
+        }
+
+        let pool = &accounts.pool;
+        let signer_seeds: &[&[u8]] = &[b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref(), &[pool.bump]];
+        let signers = &[signer_seeds];
         let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.jupiter_program.to_account_info(),
-            accounts,
+            accounts.token_program.to_account_info(),
+            MintTo {
+                mint: accounts.lp_mint.to_account_info(),
+                to: accounts.user_lp.to_account_info(),
+                authority: pool.to_account_info(),
+            },
             signers,
         );
+        anchor_spl::token::mint_to(cpi_ctx, lp)?;
+
+        msg!("Added {} / {} for {} LP", deposit_a, deposit_b, lp);
+        Ok(())
+    }
+
+    pub fn remove_liquidity(ctx: Context<PoolLiquidity>, lp: u64, min_a: u64, min_b: u64) -> Result<()> {
+        require!(lp > 0, CustomError::ZeroAmount);
+        let accounts = &ctx.accounts;
+        // withdraw_amounts divides by the supply, and no more than the supply can be burned
+        require!(
+            accounts.lp_mint.supply > 0 && lp <= accounts.lp_mint.supply,
+            CustomError::InsufficientLiquidity
+        );
+        let (amount_a, amount_b) = amm::withdraw_amounts(lp, accounts.vault_a.amount, accounts.vault_b.amount, accounts.lp_mint.supply);
+        require!(amount_a >= min_a && amount_b >= min_b, CustomError::SlippageExceeded);
+
+        let cpi_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            Burn {
+                mint: accounts.lp_mint.to_account_info(),
+                from: accounts.user_lp.to_account_info(),
+                authority: accounts.user.to_account_info(),
+            },
+        );
+        anchor_spl::token::burn(cpi_ctx, lp)?;
+
+        let pool = &accounts.pool;
+        let signer_seeds: &[&[u8]] = &[b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref(), &[pool.bump]];
+        let signers = &[signer_seeds];
+        for (from, to, amount) in [
+            (&accounts.vault_a, &accounts.user_token_a, amount_a),
+            (&accounts.vault_b, &accounts.user_token_b, amount_b),
+        ] {
+            let cpi_ctx = CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                Transfer {
+                    from: from.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: pool.to_account_info(),
+                },
+                signers,
+            );
+            anchor_spl::token::transfer(cpi_ctx, amount)?;
+        }
 
-        shared_accounts_route(
-            cpi_ctx,
-            id,
-            route_plan,
-            amount,
-            quoted_out_amount,
-            slippage_bps,
-            platform_fee_bps,
-        ).map_err(|_| CustomError::JupiterSwapFailed)?;
-
-        msg!("Jupiter swap executed successfully");
+        msg!("Removed {} LP for {} / {}", lp, amount_a, amount_b);
+        Ok(())
+    }
+
+    // direct swap without a commitment; the direction is set by which vault is passed as vault_in
+    pub fn swap(ctx: Context<PoolSwap>, amount_in: u64, min_out: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        swap_through_pool(
+            &accounts.pool,
+            &accounts.vault_in,
+            &accounts.vault_out,
+            &accounts.user_source.to_account_info(),
+            &accounts.user_destination.to_account_info(),
+            &accounts.user.to_account_info(),
+            &accounts.token_program,
+            amount_in,
+            min_out,
+        )?;
         Ok(())
     }
 
@@ -98,6 +298,51 @@ pub mod dex {
         Ok(())
     }
 
+    // Clears the session's commitment but keeps the account and its nonce, so an intent signed for
+    // an earlier commitment can never become valid again. Sessions opened before nonces and expiry
+    // slots were added are too small to deserialize as SwapSession; only the owner field, which
+    // sits at the same offset in both layouts, is read before they are grown to the current layout,
+    // the user paying the extra rent.
+    pub fn reset_session(ctx: Context<ResetSession>) -> Result<()> {
+        let session = ctx.accounts.session.to_account_info();
+        let user = ctx.accounts.user.to_account_info();
+        {
+            let data = session.try_borrow_data()?;
+            require!(data.len() >= 8 + 32, CustomError::Unauthorized);
+            require!(data[8..40] == user.key().to_bytes(), CustomError::Unauthorized);
+        }
+
+        if session.data_len() < SwapSession::SPACE {
+            let top_up = Rent::get()?
+                .minimum_balance(SwapSession::SPACE)
+                .saturating_sub(session.lamports());
+            if top_up > 0 {
+                let cpi_ctx = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer { from: user.clone(), to: session.clone() },
+                );
+                anchor_lang::system_program::transfer(cpi_ctx, top_up)?;
+            }
+            // the new nonce and expiry slot start zeroed
+            session.realloc(SwapSession::SPACE, true)?;
+        }
+
+        let mut data = session.try_borrow_mut_data()?;
+        let mut state = SwapSession::try_deserialize(&mut &data[..])?;
+        let pending = state.commitment != [0u8; 32]
+            && !state.revealed
+            && Clock::get()?.slot <= state.expiry_slot;
+        require!(!pending, CustomError::CommitmentPending);
+
+        state.commitment = [0u8; 32];
+        state.revealed = false;
+        state.expiry_slot = 0;
+        let mut writer: &mut [u8] = &mut data[..];
+        state.try_serialize(&mut writer)?;
+        msg!("Swap session reset at nonce {}", state.nonce);
+        Ok(())
+    }
+
     pub fn collect_protocol_fee(ctx: Context<CollectProtocolFee>, amount: u64) -> Result<()> {
         let session = &ctx.accounts.session;
         require!(session.revealed, CustomError::SwapNotRevealed);
@@ -128,15 +373,66 @@ pub mod dex {
     }
 }
 
+// moves `amount_in` from `source` (owned by `authority`) into the pool and pays the
+// constant-product output to `destination`, failing below `min_out`
+#[allow(clippy::too_many_arguments)]
+fn swap_through_pool<'info>(
+    pool: &Account<'info, Pool>,
+    vault_in: &Account<'info, TokenAccount>,
+    vault_out: &Account<'info, TokenAccount>,
+    source: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount_in: u64,
+    min_out: u64,
+) -> Result<u64> {
+    require!(amount_in > 0, CustomError::ZeroAmount);
+    let vaults = (vault_in.key(), vault_out.key());
+    require!(
+        vaults == (pool.vault_a, pool.vault_b) || vaults == (pool.vault_b, pool.vault_a),
+        CustomError::PoolMismatch
+    );
+    let amount_out = amm::swap_out(amount_in, vault_in.amount, vault_out.amount, pool.fee_bps)
+        .ok_or(CustomError::InsufficientLiquidity)?;
+    require!(amount_out > 0, CustomError::InsufficientLiquidity);
+    require!(amount_out >= min_out, CustomError::SlippageExceeded);
+
+    let cpi_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        Transfer {
+            from: source.clone(),
+            to: vault_in.to_account_info(),
+            authority: authority.clone(),
+        },
+    );
+    anchor_spl::token::transfer(cpi_ctx, amount_in)?;
+
+    let signer_seeds: &[&[u8]] = &[b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref(), &[pool.bump]];
+    let signers = &[signer_seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: vault_out.to_account_info(),
+            to: destination.clone(),
+            authority: pool.to_account_info(),
+        },
+        signers,
+    );
+    anchor_spl::token::transfer(cpi_ctx, amount_out)?;
+
+    msg!("Pool swap: {} in, {} out", amount_in, amount_out);
+    Ok(amount_out)
+}
+
 #[derive(Accounts)]
-#[instruction(commitment: [u8; 32])]
-pub struct CommitSwap<'info> {
+pub struct InitSession<'info> {
     #[account(
-        init_if_needed,
+        init,
         payer = user,
         seeds = [b"session", user.key().as_ref()],
         bump,
-        space = 8 + 32 + 32 + 1 + 1
+        space = SwapSession::SPACE
     )]
     pub session: Account<'info, SwapSession>,
     #[account(mut)]
@@ -145,7 +441,19 @@ pub struct CommitSwap<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(salt: [u8; 32], token_in: Pubkey, token_out: Pubkey, amount: u64)]
+#[instruction(commitment: [u8; 32])]
+pub struct CommitSwap<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", user.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Account<'info, SwapSession>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
 pub struct RevealSwap<'info> {
     #[account(
         mut,
@@ -157,17 +465,32 @@ pub struct RevealSwap<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub jupiter_program: Program<'info, jupiter_cpi::program::Jupiter>,
     pub token_program: Program<'info, Token>,
-    pub user_transfer_authority: AccountInfo<'info>,
+    #[account(mut)]
     pub source_token_account: Account<'info, TokenAccount>,
-    pub program_source_token_account: Account<'info, TokenAccount>,
-    pub program_destination_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
     pub destination_token_account: Account<'info, TokenAccount>,
     pub source_mint: AccountInfo<'info>,
     pub destination_mint: AccountInfo<'info>,
-    pub platform_fee_account: Account<'info, TokenAccount>,
-    pub token_2022_program: AccountInfo<'info>,
+
+    // only for SwapRoute::Jupiter
+    pub jupiter_program: Option<Program<'info, jupiter_cpi::program::Jupiter>>,
+    pub user_transfer_authority: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub program_source_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub program_destination_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub platform_fee_account: Option<Account<'info, TokenAccount>>,
+    pub token_2022_program: Option<AccountInfo<'info>>,
+
+    // only for SwapRoute::Pool
+    #[account(seeds = [b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref()], bump = pool.bump)]
+    pub pool: Option<Account<'info, Pool>>,
+    #[account(mut)]
+    pub pool_vault_in: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub pool_vault_out: Option<Account<'info, TokenAccount>>,
 
     pub system_program: Program<'info, System>,
 }
@@ -184,6 +507,22 @@ pub struct CancelCommitment<'info> {
     pub user: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ResetSession<'info> {
+    /// CHECK: may still hold the pre-nonce layout; the PDA seeds, program ownership and the stored
+    /// user are checked instead of deserializing it
+    #[account(
+        mut,
+        seeds = [b"session", user.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub session: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct CollectProtocolFee<'info> {
     #[account(
@@ -200,12 +539,117 @@ pub struct CollectProtocolFee<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct CreatePool<'info> {
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump,
+        space = 8 + 32 * 5 + 2 + 1
+    )]
+    pub pool: Account<'info, Pool>,
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_vault", pool.key().as_ref(), mint_a.key().as_ref()],
+        bump,
+        token::mint = mint_a,
+        token::authority = pool
+    )]
+    pub vault_a: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_vault", pool.key().as_ref(), mint_b.key().as_ref()],
+        bump,
+        token::mint = mint_b,
+        token::authority = pool
+    )]
+    pub vault_b: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+        mint::decimals = 9,
+        mint::authority = pool
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct PoolLiquidity<'info> {
+    #[account(
+        seeds = [b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref()],
+        bump = pool.bump,
+        has_one = vault_a,
+        has_one = vault_b,
+        has_one = lp_mint
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub vault_a: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_b: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = pool.mint_a, token::authority = user)]
+    pub user_token_a: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = pool.mint_b, token::authority = user)]
+    pub user_token_b: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = lp_mint, token::authority = user)]
+    pub user_lp: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct PoolSwap<'info> {
+    #[account(seeds = [b"pool", pool.mint_a.as_ref(), pool.mint_b.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub vault_in: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_out: Account<'info, TokenAccount>,
+    #[account(mut, token::authority = user)]
+    pub user_source: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_destination: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct Pool {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub lp_mint: Pubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
 #[account]
 pub struct SwapSession {
     pub user: Pubkey,
     pub commitment: [u8; 32],
     pub revealed: bool,
     pub bump: u8,
+    pub nonce: u64, // nonce of the current commitment, only ever goes up
+    pub expiry_slot: u64, // last slot the current commitment can be revealed in
+}
+
+impl SwapSession {
+    pub const SPACE: usize = 8 + 32 + 32 + 1 + 1 + 8 + 8;
 }
 
 #[error_code]
@@ -216,9 +660,43 @@ pub enum CustomError {
     AlreadyRevealed,
     #[msg("Commitment mismatch")]
     CommitmentMismatch,
+    // never returned: a failing Jupiter CPI aborts the transaction with Jupiter's own error before
+    // the dex can map it; kept so the codes after it don't shift
     #[msg("Jupiter swap failed")]
     JupiterSwapFailed,
     #[msg("Swap not revealed yet")]
     SwapNotRevealed,
+    #[msg("Nonce must be greater than the last commitment's")]
+    NonceNotIncreasing,
+    #[msg("Expiry slot must be in the future")]
+    InvalidExpiry,
+    #[msg("Nonce does not match the current commitment")]
+    StaleNonce,
+    #[msg("Commitment has expired")]
+    CommitmentExpired,
+    #[msg("Unsupported swap intent version")]
+    UnsupportedIntentVersion,
+    #[msg("Route plan does not match the committed route")]
+    RouteMismatch,
+    #[msg("Mint accounts do not match the committed tokens")]
+    MintMismatch,
+    #[msg("Swap output is below the committed minimum")]
+    SlippageExceeded,
+    #[msg("Accounts for the chosen route are missing")]
+    MissingRouteAccounts,
+    #[msg("Pool mints must be distinct and ordered mint_a < mint_b")]
+    InvalidPoolMints,
+    #[msg("Pool fee is too high")]
+    InvalidFee,
+    #[msg("Vaults do not belong to this pool")]
+    PoolMismatch,
+    #[msg("Not enough liquidity in the pool")]
+    InsufficientLiquidity,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Swap intent could not be encoded")]
+    IntentEncodingFailed,
+    #[msg("A commitment is still waiting to be revealed")]
+    CommitmentPending,
 }
 
@@ -0,0 +1,349 @@
+// reveal_and_swap against the mock Jupiter program, loaded at Jupiter's program id. Both
+// programs are loaded as built .so files, so run `anchor build` first and point SBF_OUT_DIR at
+// target/deploy; they then run as they would on chain, CPI failures included.
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::{system_program, AccountSerialize, InstructionData, ToAccountMetas};
+use dex::{CustomError, Pool, SwapIntent, SwapRoute, SWAP_INTENT_VERSION};
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+const AMOUNT_IN: u64 = 1_000_000;
+const LIQUIDITY: u64 = 1_000_000_000;
+const EXPIRY_SLOT: u64 = 1_000;
+
+struct Env {
+    context: ProgramTestContext,
+    user: Keypair,
+    session: Pubkey,
+    mint_in: Pubkey,
+    mint_out: Pubkey,
+    source: Pubkey,
+    program_source: Pubkey,
+    program_destination: Pubkey,
+    destination: Pubkey,
+    platform_fee: Pubkey,
+    pool: Pubkey,
+    pool_vault_in: Pubkey,
+    pool_vault_out: Pubkey,
+}
+
+fn mint_account() -> Account {
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    let mint = spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: u64::MAX,
+        decimals: 6,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    spl_token::state::Mint::pack(mint, &mut data).unwrap();
+    Account { lamports: 1_000_000_000, data, owner: spl_token::id(), executable: false, rent_epoch: 0 }
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    let account = spl_token::state::Account {
+        mint,
+        owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    spl_token::state::Account::pack(account, &mut data).unwrap();
+    Account { lamports: 1_000_000_000, data, owner: spl_token::id(), executable: false, rent_epoch: 0 }
+}
+
+// a native pool on the mint pair with LIQUIDITY on both sides and a 0.3% fee, written straight
+// into the bank since only the reveal is under test here
+fn add_pool(program_test: &mut ProgramTest, mint_in: Pubkey, mint_out: Pubkey) -> (Pubkey, Pubkey, Pubkey) {
+    let (mint_a, mint_b) = if mint_in < mint_out { (mint_in, mint_out) } else { (mint_out, mint_in) };
+    let (pool, bump) = Pubkey::find_program_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &dex::ID);
+    let (vault_in, vault_out) = (Pubkey::new_unique(), Pubkey::new_unique());
+    program_test.add_account(vault_in, token_account(mint_in, pool, LIQUIDITY));
+    program_test.add_account(vault_out, token_account(mint_out, pool, LIQUIDITY));
+
+    let (vault_a, vault_b) = if mint_a == mint_in { (vault_in, vault_out) } else { (vault_out, vault_in) };
+    let state = Pool { mint_a, mint_b, vault_a, vault_b, lp_mint: Pubkey::new_unique(), fee_bps: 30, bump };
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    program_test.add_account(pool, Account { lamports: 1_000_000_000, data, owner: dex::ID, executable: false, rent_epoch: 0 });
+    (pool, vault_in, vault_out)
+}
+
+// the user holds AMOUNT_IN of the source mint and, standing in for Jupiter's own accounts, the
+// liquidity the mock pays out of
+async fn setup() -> Env {
+    let mut program_test = ProgramTest::new("dex", dex::ID, None);
+    program_test.add_program("mock_jupiter", mock_jupiter::ID, None);
+
+    let user = Keypair::new();
+    program_test.add_account(
+        user.pubkey(),
+        Account { lamports: 10_000_000_000, data: vec![], owner: system_program::ID, executable: false, rent_epoch: 0 },
+    );
+    let (mint_in, mint_out) = (Pubkey::new_unique(), Pubkey::new_unique());
+    program_test.add_account(mint_in, mint_account());
+    program_test.add_account(mint_out, mint_account());
+
+    let accounts = [
+        (mint_in, AMOUNT_IN),
+        (mint_in, 0),
+        (mint_out, LIQUIDITY),
+        (mint_out, 0),
+        (mint_out, 0),
+    ];
+    let keys: Vec<Pubkey> = accounts
+        .iter()
+        .map(|(mint, amount)| {
+            let key = Pubkey::new_unique();
+            program_test.add_account(key, token_account(*mint, user.pubkey(), *amount));
+            key
+        })
+        .collect();
+    let (pool, pool_vault_in, pool_vault_out) = add_pool(&mut program_test, mint_in, mint_out);
+
+    let (session, _) = Pubkey::find_program_address(&[b"session", user.pubkey().as_ref()], &dex::ID);
+    Env {
+        context: program_test.start_with_context().await,
+        user,
+        session,
+        mint_in,
+        mint_out,
+        source: keys[0],
+        program_source: keys[1],
+        program_destination: keys[2],
+        destination: keys[3],
+        platform_fee: keys[4],
+        pool,
+        pool_vault_in,
+        pool_vault_out,
+    }
+}
+
+impl Env {
+    fn intent(&self, min_out: u64, slippage_bps: u16) -> SwapIntent {
+        SwapIntent {
+            version: SWAP_INTENT_VERSION,
+            user: self.user.pubkey(),
+            token_in: self.mint_in,
+            token_out: self.mint_out,
+            amount_in: AMOUNT_IN,
+            min_out,
+            slippage_bps,
+            route_hash: dex::intent::route_hash::<jupiter_cpi::RoutePlanStep>(&[]).unwrap(),
+            nonce: 1,
+            expiry_slot: EXPIRY_SLOT,
+            salt: [7; 32],
+        }
+    }
+
+    async fn send(&mut self, instructions: &[Instruction]) -> Result<(), BanksClientError> {
+        let blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(instructions, Some(&self.user.pubkey()), &[&self.user], blockhash);
+        self.context.banks_client.process_transaction(transaction).await
+    }
+
+    async fn commit(&mut self, intent: &SwapIntent) {
+        let init = Instruction {
+            program_id: dex::ID,
+            accounts: dex::accounts::InitSession { session: self.session, user: self.user.pubkey(), system_program: system_program::ID }
+                .to_account_metas(None),
+            data: dex::instruction::InitSession {}.data(),
+        };
+        let commit = Instruction {
+            program_id: dex::ID,
+            accounts: dex::accounts::CommitSwap { session: self.session, user: self.user.pubkey() }.to_account_metas(None),
+            data: dex::instruction::CommitSwap { commitment: intent.commitment().unwrap(), nonce: intent.nonce, expiry_slot: intent.expiry_slot }
+                .data(),
+        };
+        self.send(&[init, commit]).await.unwrap();
+    }
+
+    fn pool_intent(&self, min_out: u64) -> SwapIntent {
+        SwapIntent { route_hash: dex::intent::pool_route_hash(&self.pool), ..self.intent(min_out, 0) }
+    }
+
+    async fn reveal(&mut self, intent: SwapIntent, quoted_out_amount: u64, platform_fee_bps: u8) -> Result<(), BanksClientError> {
+        let route = SwapRoute::Jupiter { id: 0, route_plan: vec![], quoted_out_amount, platform_fee_bps };
+        let accounts = dex::accounts::RevealSwap {
+            session: self.session,
+            user: self.user.pubkey(),
+            token_program: spl_token::id(),
+            source_token_account: self.source,
+            destination_token_account: self.destination,
+            source_mint: self.mint_in,
+            destination_mint: self.mint_out,
+            jupiter_program: Some(jupiter_cpi::ID),
+            user_transfer_authority: Some(self.user.pubkey()),
+            program_source_token_account: Some(self.program_source),
+            program_destination_token_account: Some(self.program_destination),
+            platform_fee_account: Some(self.platform_fee),
+            token_2022_program: Some(spl_token::id()),
+            pool: None,
+            pool_vault_in: None,
+            pool_vault_out: None,
+            system_program: system_program::ID,
+        };
+        let reveal = Instruction {
+            program_id: dex::ID,
+            accounts: accounts.to_account_metas(None),
+            data: dex::instruction::RevealAndSwap { intent, route }.data(),
+        };
+        self.send(&[reveal]).await
+    }
+
+    async fn reveal_through_pool(&mut self, intent: SwapIntent) -> Result<(), BanksClientError> {
+        let accounts = dex::accounts::RevealSwap {
+            session: self.session,
+            user: self.user.pubkey(),
+            token_program: spl_token::id(),
+            source_token_account: self.source,
+            destination_token_account: self.destination,
+            source_mint: self.mint_in,
+            destination_mint: self.mint_out,
+            jupiter_program: None,
+            user_transfer_authority: None,
+            program_source_token_account: None,
+            program_destination_token_account: None,
+            platform_fee_account: None,
+            token_2022_program: None,
+            pool: Some(self.pool),
+            pool_vault_in: Some(self.pool_vault_in),
+            pool_vault_out: Some(self.pool_vault_out),
+            system_program: system_program::ID,
+        };
+        let reveal = Instruction {
+            program_id: dex::ID,
+            accounts: accounts.to_account_metas(None),
+            data: dex::instruction::RevealAndSwap { intent, route: SwapRoute::Pool }.data(),
+        };
+        self.send(&[reveal]).await
+    }
+
+    async fn balance(&mut self, token_account: Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(token_account).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    async fn revealed(&mut self) -> bool {
+        let account = self.context.banks_client.get_account(self.session).await.unwrap().unwrap();
+        let session: dex::SwapSession = anchor_lang::AccountDeserialize::try_deserialize(&mut account.data.as_slice()).unwrap();
+        session.revealed
+    }
+}
+
+fn custom_error(result: Result<(), BanksClientError>) -> u32 {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        err => panic!("expected a custom program error, got {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn swaps_at_the_mock_price() {
+    let mut env = setup().await;
+    let quoted = mock_jupiter::quote(AMOUNT_IN);
+    let intent = env.intent(quoted * 9_950 / 10_000, 50);
+    env.commit(&intent).await;
+
+    env.reveal(intent, quoted, 0).await.unwrap();
+
+    assert_eq!(env.balance(env.destination).await, quoted);
+    assert_eq!(env.balance(env.source).await, 0);
+    assert!(env.revealed().await);
+}
+
+#[tokio::test]
+async fn quote_that_undercuts_min_out_is_rejected() {
+    let mut env = setup().await;
+    let quoted = mock_jupiter::quote(AMOUNT_IN);
+    // the committed minimum is the full quote, but the reveal allows 0.5% slippage below it
+    let intent = env.intent(quoted, 50);
+    env.commit(&intent).await;
+
+    let code = custom_error(env.reveal(intent, quoted, 0).await);
+    assert_eq!(code, u32::from(CustomError::SlippageExceeded));
+    assert!(!env.revealed().await);
+}
+
+#[tokio::test]
+async fn output_below_min_out_is_rejected() {
+    let mut env = setup().await;
+    let quoted = mock_jupiter::quote(AMOUNT_IN);
+    let intent = env.intent(quoted * 9_950 / 10_000, 50);
+    env.commit(&intent).await;
+
+    // a 1% platform fee leaves the destination short of the committed minimum
+    let code = custom_error(env.reveal(intent, quoted, 100).await);
+    assert_eq!(code, u32::from(CustomError::SlippageExceeded));
+    assert_eq!(env.balance(env.destination).await, 0);
+}
+
+#[tokio::test]
+async fn jupiter_failures_abort_with_jupiters_error() {
+    let mut env = setup().await;
+    // quoting 1.5x what the mock fills at trips its own slippage check; a failing callee aborts
+    // the whole transaction with its own error, so the dex never gets to see it
+    let quoted = mock_jupiter::quote(AMOUNT_IN) * 3 / 2;
+    let intent = env.intent(quoted * 9_950 / 10_000, 50);
+    env.commit(&intent).await;
+
+    let code = custom_error(env.reveal(intent, quoted, 0).await);
+    assert_eq!(code, u32::from(mock_jupiter::MockJupiterError::SlippageToleranceExceeded));
+    assert!(!env.revealed().await);
+}
+
+#[tokio::test]
+async fn a_revealed_intent_cannot_be_replayed() {
+    let mut env = setup().await;
+    let quoted = mock_jupiter::quote(AMOUNT_IN);
+    let intent = env.intent(0, 50);
+    env.commit(&intent).await;
+    env.reveal(intent.clone(), quoted, 0).await.unwrap();
+
+    // a different quote, so the bank doesn't drop it as an already processed transaction
+    let code = custom_error(env.reveal(intent, quoted + 1, 0).await);
+    assert_eq!(code, u32::from(CustomError::AlreadyRevealed));
+}
+
+#[tokio::test]
+async fn swaps_through_a_native_pool() {
+    let mut env = setup().await;
+    let expected = dex::amm::swap_out(AMOUNT_IN, LIQUIDITY, LIQUIDITY, 30).unwrap();
+    let intent = env.pool_intent(expected);
+    env.commit(&intent).await;
+
+    env.reveal_through_pool(intent).await.unwrap();
+
+    assert_eq!(env.balance(env.destination).await, expected);
+    assert_eq!(env.balance(env.source).await, 0);
+    assert_eq!(env.balance(env.pool_vault_in).await, LIQUIDITY + AMOUNT_IN);
+    assert_eq!(env.balance(env.pool_vault_out).await, LIQUIDITY - expected);
+    assert!(env.revealed().await);
+}
+
+#[tokio::test]
+async fn pool_reveals_are_held_to_the_committed_route_and_minimum() {
+    let mut env = setup().await;
+    let expected = dex::amm::swap_out(AMOUNT_IN, LIQUIDITY, LIQUIDITY, 30).unwrap();
+
+    // an intent committed to the Jupiter route can't be sent through the pool
+    let intent = env.intent(0, 0);
+    env.commit(&intent).await;
+    let code = custom_error(env.reveal_through_pool(intent).await);
+    assert_eq!(code, u32::from(CustomError::RouteMismatch));
+
+    // and a pool swap that can't pay the committed minimum fails
+    let mut env = setup().await;
+    let intent = env.pool_intent(expected + 1);
+    env.commit(&intent).await;
+    let code = custom_error(env.reveal_through_pool(intent).await);
+    assert_eq!(code, u32::from(CustomError::SlippageExceeded));
+    assert_eq!(env.balance(env.source).await, AMOUNT_IN);
+    assert!(!env.revealed().await);
+}
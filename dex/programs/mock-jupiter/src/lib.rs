@@ -0,0 +1,112 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+
+// Deployed at Jupiter's program id on localnet and in program tests, so the dex's
+// reveal_and_swap can be exercised without the real aggregator. The instruction name, argument
+// order and account order match jupiter-cpi's shared_accounts_route; the route plan is ignored
+// and every swap fills at PRICE.
+declare_id!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+// destination units paid per source unit, scaled by PRICE_SCALE
+pub const PRICE: u64 = 2_000_000;
+pub const PRICE_SCALE: u64 = 1_000_000;
+
+pub fn quote(in_amount: u64) -> u64 {
+    (in_amount as u128 * PRICE as u128 / PRICE_SCALE as u128) as u64
+}
+
+#[program]
+pub mod mock_jupiter {
+    use super::*;
+
+    // pulls `in_amount` from the source account into the program source account, and pays the
+    // quoted output less the platform fee out of the program destination account. The mock has no
+    // authority of its own, so both legs are signed by `user_transfer_authority` and the program
+    // destination account has to be owned by it and funded up front.
+    pub fn shared_accounts_route(
+        ctx: Context<SharedAccountsRoute>,
+        _id: u64,
+        _route_plan: Vec<jupiter_cpi::RoutePlanStep>,
+        in_amount: u64,
+        quoted_out_amount: u64,
+        slippage_bps: u16,
+        platform_fee_bps: u8,
+    ) -> Result<u64> {
+        require!(slippage_bps <= 10_000, MockJupiterError::InvalidSlippage);
+        let out_amount = quote(in_amount);
+        let min_out = quoted_out_amount as u128 * (10_000 - slippage_bps) as u128 / 10_000;
+        require!(out_amount as u128 >= min_out, MockJupiterError::SlippageToleranceExceeded);
+        let fee = out_amount * platform_fee_bps as u64 / 10_000;
+
+        let accounts = &ctx.accounts;
+        let transfer = |from: &Account<'_, TokenAccount>, to: AccountInfo<'_>, amount: u64| {
+            if amount == 0 {
+                return Ok(());
+            }
+            let cpi_ctx = CpiContext::new(
+                accounts.token_program.to_account_info(),
+                Transfer {
+                    from: from.to_account_info(),
+                    to,
+                    authority: accounts.user_transfer_authority.to_account_info(),
+                },
+            );
+            anchor_spl::token::transfer(cpi_ctx, amount)
+        };
+        transfer(
+            &accounts.source_token_account,
+            accounts.program_source_token_account.to_account_info(),
+            in_amount,
+        )?;
+        transfer(
+            &accounts.program_destination_token_account,
+            accounts.destination_token_account.to_account_info(),
+            out_amount - fee,
+        )?;
+        transfer(
+            &accounts.program_destination_token_account,
+            accounts.platform_fee_account.to_account_info(),
+            fee,
+        )?;
+
+        msg!("Mock route filled {} for {}", in_amount, out_amount - fee);
+        Ok(out_amount - fee)
+    }
+}
+
+#[derive(Accounts)]
+pub struct SharedAccountsRoute<'info> {
+    pub token_program: Program<'info, Token>,
+    /// CHECK: not read by the mock
+    pub program_authority: AccountInfo<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    #[account(mut)]
+    pub source_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub program_source_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub program_destination_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    /// CHECK: not read by the mock
+    pub source_mint: AccountInfo<'info>,
+    /// CHECK: not read by the mock
+    pub destination_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub platform_fee_account: Account<'info, TokenAccount>,
+    /// CHECK: not read by the mock
+    pub token_2022_program: AccountInfo<'info>,
+}
+
+// same codes as the real program's errors
+#[error_code(offset = 6001)]
+pub enum MockJupiterError {
+    #[msg("Slippage tolerance exceeded")]
+    SlippageToleranceExceeded,
+    #[msg("Invalid calculation")]
+    InvalidCalculation,
+    #[msg("Missing platform fee account")]
+    MissingPlatformFeeAccount,
+    #[msg("Invalid slippage")]
+    InvalidSlippage,
+}
@@ -5,19 +5,23 @@ import { Dex } from "../target/types/dex";
 import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
 import { TOKEN_PROGRAM_ID, createMint, createAccount, mintTo } from "@solana/spl-token";
 import { assert } from "chai";
+import keccak256 from "keccak256";
 
 describe("dex", () => {
   const provider = anchor.AnchorProvider.env();
   anchor.setProvider(provider);
   const program = anchor.workspace.Dex as Program<Dex>;
   const user = provider.wallet as anchor.Wallet;
+  // the mock-jupiter program is deployed at Jupiter's address on localnet
+  const JUPITER_PROGRAM_ID = new PublicKey("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
 
   let mintA: PublicKey;
   let mintB: PublicKey;
   let userTokenA: PublicKey;
   let userTokenB: PublicKey;
-  let programTokenA: PublicKey;   
+  let programTokenA: PublicKey;
   let programTokenB: PublicKey;
+  let feeVault: PublicKey;
   let treasuryToken: PublicKey;
   let sessionPda: PublicKey;
   let sessionBump: number;
@@ -28,98 +32,163 @@ describe("dex", () => {
     mintA = await createMint(provider.connection, user.payer, user.publicKey, null, 6);
     mintB = await createMint(provider.connection, user.payer, user.publicKey, null, 6);
 
-    // Create token accounts
+    // Derive session PDA
+    [sessionPda, sessionBump] = PublicKey.findProgramAddressSync(
+      [Buffer.from("session"), user.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // Create token accounts. The mock Jupiter signs both legs of a swap with the user, so the
+    // liquidity it pays out of (standing in for Jupiter's own account) has to be the user's too
     userTokenA = await createAccount(provider.connection, user.payer, mintA, user.publicKey);
     userTokenB = await createAccount(provider.connection, user.payer, mintB, user.publicKey);
     programTokenA = await createAccount(provider.connection, user.payer, mintA, program.programId);
-    programTokenB = await createAccount(provider.connection, user.payer, mintB, program.programId);
+    programTokenB = await createAccount(provider.connection, user.payer, mintB, user.publicKey, Keypair.generate());
+    // protocol fees are paid out of an account the session signs for
+    feeVault = await createAccount(provider.connection, user.payer, mintA, sessionPda, Keypair.generate());
     treasuryToken = await createAccount(provider.connection, user.payer, mintA, program.programId);
 
     // Mint some tokens to user
     await mintTo(provider.connection, user.payer, mintA, userTokenA, user.publicKey, 1_000_000_000);
-  await mintTo(provider.connection, user.payer, mintA, programTokenA, user.publicKey, 1_000_000_000);
-  await mintTo(provider.connection, user.payer, mintB, programTokenB, user.publicKey, 1_000_000_000);
+    await mintTo(provider.connection, user.payer, mintB, programTokenB, user.publicKey, 1_000_000_000);
+    await mintTo(provider.connection, user.payer, mintA, feeVault, user.publicKey, 1_000_000_000);
+  });
 
-    // Derive session PDA
-    [sessionPda, sessionBump] = PublicKey.findProgramAddressSync(
-      [Buffer.from("session"), user.publicKey.toBuffer()],
-      program.programId
+  const salt = Buffer.alloc(32, 9); // dummy
+  const amount = new anchor.BN(100_000);
+  const nonce = new anchor.BN(1);
+  let expirySlot: anchor.BN;
+
+  // the committed SwapIntent; the route plan is empty for the mock, so its hash covers a zero length
+  const intentFor = (nonce: anchor.BN) => ({
+    version: 1,
+    user: user.publicKey,
+    tokenIn: mintA,
+    tokenOut: mintB,
+    amountIn: amount,
+    minOut: new anchor.BN(0),
+    slippageBps: 50,
+    routeHash: [...keccak256(Buffer.alloc(4))],
+    nonce,
+    expirySlot,
+    salt: [...salt],
+  });
+  // keccak256 of the Borsh-encoded intent, the same bytes the dex-client crate hashes
+  const commitmentFor = (intent: ReturnType<typeof intentFor>) =>
+    keccak256(program.coder.types.encode("SwapIntent", intent));
+
+  // accounts every route needs, and the ones only one of them does
+  const swapAccounts = () => ({
+    tokenProgram: TOKEN_PROGRAM_ID,
+    sourceTokenAccount: userTokenA,
+    destinationTokenAccount: userTokenB,
+    sourceMint: mintA,
+    destinationMint: mintB,
+    systemProgram: SystemProgram.programId,
+  });
+  const jupiterAccounts = () => ({
+    userTransferAuthority: user.publicKey,
+    programSourceTokenAccount: programTokenA,
+    programDestinationTokenAccount: programTokenB,
+    platformFeeAccount: treasuryToken,
+    token2022Program: TOKEN_PROGRAM_ID,
+  });
+  const noJupiter = {
+    jupiterProgram: null,
+    userTransferAuthority: null,
+    programSourceTokenAccount: null,
+    programDestinationTokenAccount: null,
+    platformFeeAccount: null,
+    token2022Program: null,
+  };
+  const noPool = { pool: null, poolVaultIn: null, poolVaultOut: null };
+  // route_plan empty for mock
+  const emptyJupiterRoute = {
+    jupiter: { id: new anchor.BN(0), routePlan: [], quotedOutAmount: new anchor.BN(0), platformFeeBps: 0 },
+  };
+
+  it("hashes intents the way the program does", () => {
+    // the fixed vector from intent.rs's commitment test
+    const key = (byte: number) => new PublicKey(Buffer.alloc(32, byte));
+    const intent = {
+      version: 1,
+      user: key(1),
+      tokenIn: key(2),
+      tokenOut: key(3),
+      amountIn: new anchor.BN(1_000_000),
+      minOut: new anchor.BN(1_990_000),
+      slippageBps: 50,
+      routeHash: Array(32).fill(4),
+      nonce: new anchor.BN(7),
+      expirySlot: new anchor.BN(1_000),
+      salt: Array(32).fill(9),
+    };
+    assert.equal(
+      keccak256(program.coder.types.encode("SwapIntent", intent)).toString("hex"),
+      "066b09374351f2e7f782fcc37bcfe9a6ae880ebbed887734557e1375dae65c16"
     );
   });
 
   it("commit swap", async () => {
-    const salt = Buffer.alloc(32, 9); // dummy
-    const amount = new anchor.BN(100_000);
-    const buf = Buffer.concat([
-      mintA.toBuffer(),
-      mintB.toBuffer(),
-      Buffer.alloc(8, 0), // amount little endian placeholder
-      salt
-    ]);
-    buf.writeBigUInt64LE(BigInt(amount.toString()), 32); // fix encoding
-
-    const commitment = anchor.utils.sha256(buf);
+    await program.methods
+      .initSession()
+      .accounts({ session: sessionPda, user: user.publicKey, systemProgram: SystemProgram.programId })
+      .rpc();
 
+    expirySlot = new anchor.BN((await provider.connection.getSlot()) + 1_500);
+    const commitment = commitmentFor(intentFor(nonce));
     await program.methods
-      .commitSwap(commitment)
+      .commitSwap([...commitment], nonce, expirySlot)
       .accounts({
         session: sessionPda,
         user: user.publicKey,
-        systemProgram: SystemProgram.programId,
       })
       .rpc();
 
     const sess = await program.account.swapSession.fetch(sessionPda);
-    assert.deepEqual(sess.commitment, commitment);
+    assert.deepEqual(sess.commitment, [...commitment]);
     assert.isFalse(sess.revealed);
+    assert.isTrue(sess.nonce.eq(nonce));
   });
 
+  it("rejects a commitment that reuses the nonce", async () => {
+    try {
+      await program.methods
+        .commitSwap([...commitmentFor(intentFor(nonce))], nonce, expirySlot)
+        .accounts({ session: sessionPda, user: user.publicKey })
+        .rpc();
+      assert.fail("Nonce should have to increase");
+    } catch (err) {
+      assert.instanceOf(err, anchor.AnchorError);
+      assert.equal(err.error.errorCode.code, "NonceNotIncreasing");
+    }
+  });
+
+  const balance = async (account: PublicKey) =>
+    Number((await provider.connection.getTokenAccountBalance(account)).value.amount);
+
   it("reveal and swap (mock Jupiter)", async () => {
-    // We'll just call with dummy accounts for Jupiter CPI since we're not actually swapping
-    const salt = Buffer.alloc(32, 9);
-    const amount = new anchor.BN(100_000);
-    const buf = Buffer.concat([
-      mintA.toBuffer(),
-      mintB.toBuffer(),
-      Buffer.alloc(8, 0),
-      salt
-    ]);
-    buf.writeBigUInt64LE(BigInt(amount.toString()), 32);
-    const commitment = anchor.utils.sha256(buf);
-
-    // route_plan empty for mock
+    const sourceBefore = await balance(userTokenA);
+    const destinationBefore = await balance(userTokenB);
+
     await program.methods
-      .revealAndSwap(
-        [...salt],
-        mintA,
-        mintB,
-        amount,
-        new anchor.BN(0), // id
-        [], // route plan
-        new anchor.BN(0), // quoted_out_amount
-        50, // slippage_bps
-        0   // platform_fee_bps
-      )
+      .revealAndSwap(intentFor(nonce), emptyJupiterRoute)
       .accounts({
         session: sessionPda,
         user: user.publicKey,
-        jupiterProgram: JUPITER_PROGRAM_ID, // we can deploy a mock program here if needed
-        tokenProgram: TOKEN_PROGRAM_ID,
-        userTransferAuthority: user.publicKey,
-        sourceTokenAccount: userTokenA,
-        programSourceTokenAccount: programTokenA,
-        programDestinationTokenAccount: programTokenB,
-        destinationTokenAccount: userTokenB,
-        sourceMint: mintA,
-        destinationMint: mintB,
-        platformFeeAccount: treasuryToken,
-        token2022Program: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
+        ...swapAccounts(),
+        jupiterProgram: JUPITER_PROGRAM_ID,
+        ...jupiterAccounts(),
+        ...noPool,
       })
       .rpc();
 
     const sess = await program.account.swapSession.fetch(sessionPda);
     assert.isTrue(sess.revealed);
+    // the mock fills every swap at 2 out per 1 in
+    assert.equal(sourceBefore - (await balance(userTokenA)), 100_000);
+    assert.equal((await balance(userTokenB)) - destinationBefore, 200_000);
+    assert.equal(await balance(programTokenA), 100_000);
   });
 
   it("collect protocol fee", async () => {
@@ -130,12 +199,140 @@ describe("dex", () => {
       .accounts({
         session: sessionPda,
         user: user.publicKey,
-        sourceTokenAccount: programTokenA,
+        sourceTokenAccount: feeVault,
         treasuryTokenAccount: treasuryToken,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
 
-    // In a real test, fetch balances and check fee deduction
+    // 0.1% of the amount
+    assert.equal(await balance(treasuryToken), 1_000);
+    assert.equal(await balance(feeVault), 1_000_000_000 - 1_000);
+  });
+
+  it("rejects a reveal for an older nonce", async () => {
+    // a new commitment moves the session on, so the first reveal can't be replayed against it
+    const nextNonce = nonce.addn(1);
+    await program.methods
+      .commitSwap([...commitmentFor(intentFor(nextNonce))], nextNonce, expirySlot)
+      .accounts({ session: sessionPda, user: user.publicKey })
+      .rpc();
+
+    try {
+      await program.methods
+        .revealAndSwap(intentFor(nonce), emptyJupiterRoute)
+        .accounts({
+          session: sessionPda,
+          user: user.publicKey,
+          ...swapAccounts(),
+          jupiterProgram: JUPITER_PROGRAM_ID,
+          ...jupiterAccounts(),
+          ...noPool,
+        })
+        .rpc();
+      assert.fail("Stale nonce should be rejected");
+    } catch (err) {
+      assert.instanceOf(err, anchor.AnchorError);
+      assert.equal(err.error.errorCode.code, "StaleNonce");
+    }
+  });
+  describe("native pool", () => {
+    let pool: PublicKey;
+    let vaultA: PublicKey; // vault for mintA, whichever side of the pool it sorts to
+    let vaultB: PublicKey;
+    let lpMint: PublicKey;
+    let userLp: PublicKey;
+
+    it("creates a pool and adds liquidity", async () => {
+      await mintTo(provider.connection, user.payer, mintB, userTokenB, user.publicKey, 1_000_000_000);
+      // pools are keyed by the ordered mint pair
+      const [first, second] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+      [pool] = PublicKey.findProgramAddressSync([Buffer.from("pool"), first.toBuffer(), second.toBuffer()], program.programId);
+      [vaultA] = PublicKey.findProgramAddressSync([Buffer.from("pool_vault"), pool.toBuffer(), mintA.toBuffer()], program.programId);
+      [vaultB] = PublicKey.findProgramAddressSync([Buffer.from("pool_vault"), pool.toBuffer(), mintB.toBuffer()], program.programId);
+      [lpMint] = PublicKey.findProgramAddressSync([Buffer.from("lp_mint"), pool.toBuffer()], program.programId);
+
+      await program.methods
+        .createPool(30)
+        .accounts({
+          pool,
+          mintA: first,
+          mintB: second,
+          vaultA: first.equals(mintA) ? vaultA : vaultB,
+          vaultB: first.equals(mintA) ? vaultB : vaultA,
+          lpMint,
+          payer: user.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+
+      userLp = await createAccount(provider.connection, user.payer, lpMint, user.publicKey);
+      const [userFirst, userSecond] = first.equals(mintA) ? [userTokenA, userTokenB] : [userTokenB, userTokenA];
+      await program.methods
+        .addLiquidity(new anchor.BN(100_000_000), new anchor.BN(100_000_000), new anchor.BN(0))
+        .accounts({
+          pool,
+          vaultA: first.equals(mintA) ? vaultA : vaultB,
+          vaultB: first.equals(mintA) ? vaultB : vaultA,
+          lpMint,
+          userTokenA: userFirst,
+          userTokenB: userSecond,
+          userLp,
+          user: user.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const lp = await provider.connection.getTokenAccountBalance(userLp);
+      assert.equal(lp.value.amount, "100000000", "The first deposit mints sqrt(a * b)");
+    });
+
+    it("swaps at the constant-product price", async () => {
+      const before = await provider.connection.getTokenAccountBalance(userTokenB);
+      await program.methods
+        .swap(new anchor.BN(1_000_000), new anchor.BN(0))
+        .accounts({ pool, vaultIn: vaultA, vaultOut: vaultB, userSource: userTokenA, userDestination: userTokenB, user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+        .rpc();
+      const after = await provider.connection.getTokenAccountBalance(userTokenB);
+
+      // 1_000_000 less 0.3% against 100_000_000 / 100_000_000 reserves
+      assert.equal(Number(after.value.amount) - Number(before.value.amount), 987_158);
+    });
+
+    it("routes a revealed swap through the pool", async () => {
+      const poolNonce = nonce.addn(2);
+      const intent = {
+        ...intentFor(poolNonce),
+        amountIn: new anchor.BN(1_000_000),
+        minOut: new anchor.BN(900_000),
+        slippageBps: 0,
+        routeHash: [...keccak256(Buffer.concat([Buffer.from("pool"), pool.toBuffer()]))],
+      };
+      await program.methods
+        .commitSwap([...commitmentFor(intent)], poolNonce, expirySlot)
+        .accounts({ session: sessionPda, user: user.publicKey })
+        .rpc();
+
+      const before = await provider.connection.getTokenAccountBalance(userTokenB);
+      await program.methods
+        .revealAndSwap(intent, { pool: {} })
+        .accounts({
+          session: sessionPda,
+          user: user.publicKey,
+          ...swapAccounts(),
+          ...noJupiter,
+          pool,
+          poolVaultIn: vaultA,
+          poolVaultOut: vaultB,
+        })
+        .rpc();
+      const after = await provider.connection.getTokenAccountBalance(userTokenB);
+
+      assert.isAtLeast(Number(after.value.amount) - Number(before.value.amount), 900_000);
+      const sess = await program.account.swapSession.fetch(sessionPda);
+      assert.isTrue(sess.revealed);
+    });
   });
 });
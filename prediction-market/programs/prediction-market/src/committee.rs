@@ -0,0 +1,136 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+pub const MAX_MEMBERS: usize = 5;
+
+// passed to create_market; members vote with vote_resolution
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct CommitteeConfig {
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+// resolver committee: the market resolves once `threshold` members vote for the same outcome.
+// The threshold is a strict majority, so two conflicting outcomes can never both reach it and a
+// split that leaves neither able to deadlocks the market into refunds.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Committee {
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub votes: Vec<Option<u8>>, // one slot per member, filled in by vote_resolution
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tally {
+    Pending,
+    Agreed(u8),
+    Deadlocked, // no outcome can reach the threshold any more
+}
+
+impl Committee {
+    pub const SIZE: usize = 4 + MAX_MEMBERS * 32 + 1 + 4 + MAX_MEMBERS * 2;
+
+    pub fn new(config: CommitteeConfig) -> Result<Self> {
+        let CommitteeConfig { members, threshold } = config;
+        require!(!members.is_empty() && members.len() <= MAX_MEMBERS, ErrorCode::InvalidCommittee);
+        require!(threshold as usize > members.len() / 2 && threshold as usize <= members.len(), ErrorCode::InvalidCommittee);
+        for (i, member) in members.iter().enumerate() {
+            require!(!members[..i].contains(member), ErrorCode::InvalidCommittee);
+        }
+        let votes = vec![None; members.len()];
+        Ok(Committee { members, threshold, votes })
+    }
+
+    pub fn vote(&mut self, member: &Pubkey, outcome: u8, outcome_count: usize) -> Result<Tally> {
+        let index = self.members.iter().position(|m| m == member).ok_or(ErrorCode::NotCommitteeMember)?;
+        require!(self.votes[index].is_none(), ErrorCode::AlreadyVoted);
+        self.votes[index] = Some(outcome);
+        Ok(self.tally(outcome_count))
+    }
+
+    pub fn votes_for(&self, outcome: u8) -> usize {
+        self.votes.iter().filter(|vote| **vote == Some(outcome)).count()
+    }
+
+    pub fn tally(&self, outcome_count: usize) -> Tally {
+        let outstanding = self.votes.iter().filter(|vote| vote.is_none()).count();
+        let threshold = self.threshold as usize;
+        let mut reachable = false;
+        for outcome in 0..outcome_count as u8 {
+            let votes = self.votes_for(outcome);
+            if votes >= threshold {
+                return Tally::Agreed(outcome);
+            }
+            reachable |= votes + outstanding >= threshold;
+        }
+        if reachable { Tally::Pending } else { Tally::Deadlocked }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn committee(n: usize, threshold: u8) -> (Committee, Vec<Pubkey>) {
+        let members: Vec<Pubkey> = (0..n).map(|_| Pubkey::new_unique()).collect();
+        (Committee::new(CommitteeConfig { members: members.clone(), threshold }).unwrap(), members)
+    }
+
+    #[test]
+    fn resolves_once_the_threshold_agrees() {
+        let (mut committee, members) = committee(3, 2);
+        assert_eq!(committee.vote(&members[0], 1, 2).unwrap(), Tally::Pending);
+        assert_eq!(committee.vote(&members[1], 0, 2).unwrap(), Tally::Pending);
+        assert_eq!(committee.vote(&members[2], 1, 2).unwrap(), Tally::Agreed(1));
+    }
+
+    #[test]
+    fn split_votes_deadlock() {
+        let (mut committee, members) = committee(4, 3);
+        committee.vote(&members[0], 0, 2).unwrap();
+        committee.vote(&members[1], 1, 2).unwrap();
+        assert_eq!(committee.vote(&members[2], 0, 2).unwrap(), Tally::Pending);
+        assert_eq!(committee.vote(&members[3], 1, 2).unwrap(), Tally::Deadlocked);
+    }
+
+    #[test]
+    fn conflicting_votes_never_resolve_to_the_minority() {
+        let (mut committee, members) = committee(5, 3);
+        committee.vote(&members[0], 0, 2).unwrap();
+        committee.vote(&members[1], 1, 2).unwrap();
+        committee.vote(&members[2], 1, 2).unwrap();
+        assert_eq!(committee.vote(&members[3], 0, 2).unwrap(), Tally::Pending);
+        assert_eq!(committee.vote(&members[4], 0, 2).unwrap(), Tally::Agreed(0));
+    }
+
+    #[test]
+    fn an_even_split_deadlocks() {
+        let (mut committee, members) = committee(2, 2);
+        committee.vote(&members[0], 0, 2).unwrap();
+        assert_eq!(committee.vote(&members[1], 1, 2).unwrap(), Tally::Deadlocked);
+    }
+
+    #[test]
+    fn thresholds_must_be_a_majority() {
+        let members: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let config = |threshold| CommitteeConfig { members: members.clone(), threshold };
+        assert!(Committee::new(config(0)).is_err());
+        assert!(Committee::new(config(2)).is_err());
+        assert!(Committee::new(config(3)).is_ok());
+        assert!(Committee::new(config(4)).is_ok());
+    }
+
+    #[test]
+    fn rejects_outsiders_double_votes_and_bad_configs() {
+        let (mut committee, members) = committee(2, 2);
+        assert!(committee.vote(&Pubkey::new_unique(), 0, 2).is_err());
+        committee.vote(&members[0], 0, 2).unwrap();
+        assert!(committee.vote(&members[0], 0, 2).is_err());
+
+        let config = |members: Vec<Pubkey>, threshold| CommitteeConfig { members, threshold };
+        assert!(Committee::new(config(vec![], 1)).is_err());
+        assert!(Committee::new(config(vec![members[0]], 2)).is_err());
+        assert!(Committee::new(config(vec![members[0], members[0]], 1)).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use crate::parlay::ParlayLeg;
+
+#[event]
+pub struct MarketCreated {
+    pub market: Pubkey,
+    pub id: u64,
+    pub authority: Pubkey,
+    pub question: String,
+    pub betting_close_time: i64,
+    pub resolution_deadline: i64,
+}
+
+#[event]
+pub struct BetPlaced {
+    pub market: Pubkey,
+    pub bettor: Pubkey,
+    pub outcome_index: u8,
+    pub amount: u64,
+    pub total_bets: Vec<u64>,
+}
+
+#[event]
+pub struct BetWithdrawn {
+    pub market: Pubkey,
+    pub bettor: Pubkey,
+    pub amount: u64,
+    pub exit_fee: u64,
+}
+
+// one of winning_outcome / resolved_value is set, depending on the market kind
+#[event]
+pub struct MarketResolved {
+    pub market: Pubkey,
+    pub winning_outcome: Option<u8>,
+    pub resolved_value: Option<i64>,
+    pub resolved_at: i64,
+}
+
+#[event]
+pub struct CommitteeVoted {
+    pub market: Pubkey,
+    pub member: Pubkey,
+    pub outcome: u8,
+    pub votes_for_outcome: u8,
+    pub threshold: u8,
+}
+
+// the committee split so no outcome can reach the threshold; the market is refunded
+#[event]
+pub struct CommitteeDeadlocked {
+    pub market: Pubkey,
+}
+
+// leftovers swept to the treasury by finalize_market: lamports and, for SPL markets, tokens.
+// Exit fees and the LMSR subsidy of a refundable market go back to its creator instead.
+#[event]
+pub struct MarketFinalized {
+    pub market: Pubkey,
+    pub swept: u64,
+    pub swept_tokens: u64,
+    pub returned_to_creator: u64,
+}
+
+#[event]
+pub struct PayoutClaimed {
+    pub market: Pubkey,
+    pub bettor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RefundClaimed {
+    pub market: Pubkey,
+    pub bettor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ParlayPlaced {
+    pub parlay: Pubkey,
+    pub bettor: Pubkey,
+    pub stake: u64,
+    pub legs: Vec<ParlayLeg>,
+    pub potential_payout: u64,
+}
+
+// payout is zero for a lost parlay, the stake back if every leg was refunded
+#[event]
+pub struct ParlaySettled {
+    pub parlay: Pubkey,
+    pub bettor: Pubkey,
+    pub payout: u64,
+}
+
+// returned by get_market_stats; probabilities are in basis points, pari-mutuel ones sum to
+// exactly 10_000 (or are all zero before the first bet)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MarketStats {
+    pub implied_probabilities_bps: Vec<u16>,
+    pub pool_size: u64,
+    pub bettor_count: u32,
+    pub time_left: i64, // seconds until betting closes, 0 once it has
+}
+
+// each outcome's share of the pool; rounding dust goes to the last outcome so the total stays exact
+pub fn implied_probabilities_bps(total_bets: &[u64]) -> Vec<u16> {
+    let pool: u128 = total_bets.iter().map(|total| *total as u128).sum();
+    if pool == 0 {
+        return vec![0; total_bets.len()];
+    }
+    let mut probabilities: Vec<u16> = total_bets
+        .iter()
+        .map(|total| (*total as u128 * 10_000 / pool) as u16)
+        .collect();
+    let assigned: u16 = probabilities.iter().sum();
+    if let Some(last) = probabilities.last_mut() {
+        *last += 10_000 - assigned;
+    }
+    probabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probabilities_follow_the_pool() {
+        assert_eq!(implied_probabilities_bps(&[300, 100]), vec![7_500, 2_500]);
+        assert_eq!(implied_probabilities_bps(&[1, 2]), vec![3_333, 6_667]);
+        assert_eq!(implied_probabilities_bps(&[0, 50]), vec![0, 10_000]);
+    }
+
+    #[test]
+    fn empty_pool_has_no_probabilities() {
+        assert_eq!(implied_probabilities_bps(&[0, 0]), vec![0, 0]);
+    }
+}
@@ -0,0 +1,30 @@
+// Withdrawing a bet early costs a fee that grows linearly from MIN_EXIT_FEE_BPS when the market
+// opens to MAX_EXIT_FEE_BPS at betting close, so late exits can't be used to game the final odds.
+pub const MIN_EXIT_FEE_BPS: u64 = 100;
+pub const MAX_EXIT_FEE_BPS: u64 = 1_000;
+
+pub fn exit_fee(amount: u64, created_at: i64, betting_close_time: i64, now: i64) -> u64 {
+    let duration = (betting_close_time - created_at).max(1) as u128;
+    let elapsed = (now - created_at).clamp(0, duration as i64) as u128;
+    let fee_bps = MIN_EXIT_FEE_BPS as u128 + (MAX_EXIT_FEE_BPS - MIN_EXIT_FEE_BPS) as u128 * elapsed / duration;
+    (amount as u128 * fee_bps / 10_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_grows_towards_betting_close() {
+        assert_eq!(exit_fee(10_000, 0, 100, 0), 100);
+        assert_eq!(exit_fee(10_000, 0, 100, 50), 550);
+        assert_eq!(exit_fee(10_000, 0, 100, 100), 1_000);
+    }
+
+    #[test]
+    fn clamps_times_outside_the_betting_window() {
+        assert_eq!(exit_fee(10_000, 0, 100, -5), 100);
+        assert_eq!(exit_fee(10_000, 0, 100, 500), 1_000);
+        assert_eq!(exit_fee(10_000, 100, 100, 100), 100);
+    }
+}
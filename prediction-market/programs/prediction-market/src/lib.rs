@@ -1,56 +1,502 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Burn, CloseAccount, Mint, MintTo, Token, TokenAccount, Transfer as TokenTransfer};
+
+pub mod oracle;
+pub use oracle::{Comparison, OracleConfig};
+use oracle::PriceFeed;
+pub mod optimistic;
+pub use optimistic::OptimisticConfig;
+pub mod lmsr;
+pub use lmsr::Lmsr;
+pub mod metadata;
+pub use metadata::{MarketCategory, MarketMetadata};
+pub mod scalar;
+pub use scalar::MarketKind;
+pub mod orderbook;
+pub use orderbook::{OrderBook, Side};
+pub mod limits;
+pub use limits::BetLimits;
+pub mod exit;
+pub mod committee;
+pub mod liquidity;
+pub mod payout;
+pub mod series;
+pub mod parlay;
+pub use parlay::ParlayLeg;
+use parlay::LegResult;
+use payout::Settlement;
+pub use committee::{Committee, CommitteeConfig};
+use committee::Tally;
+pub mod events;
+pub use events::MarketStats;
+use events::{
+    BetPlaced, BetWithdrawn, CommitteeDeadlocked, CommitteeVoted, MarketCreated, MarketFinalized, MarketResolved, ParlayPlaced, ParlaySettled,
+    PayoutClaimed, RefundClaimed,
+};
 
 declare_id!("2JUq1HtSi9znKbSWwe3b37xTA7V96CcHdLiFmwmmG4DA");
 
+// winners always get at least a day between the resolution deadline and finalize_market
+pub const MIN_CLAIM_WINDOW: i64 = 24 * 60 * 60;
+
 #[program]
 pub mod prediction_market {
     use super::*;
+    pub fn initialize_factory(ctx: Context<InitializeFactory>, creation_fee: u64, allowlist_enabled: bool, claim_window: i64) -> Result<()> {
+        require!(claim_window >= MIN_CLAIM_WINDOW, ErrorCode::InvalidClaimWindow);
+        let factory = &mut ctx.accounts.factory;
+        factory.admin = ctx.accounts.admin.key();
+        factory.treasury = ctx.accounts.treasury.key();
+        factory.creation_fee = creation_fee;
+        factory.allowlist_enabled = allowlist_enabled;
+        factory.claim_window = claim_window;
+        factory.market_count = 0;
+        factory.bump = ctx.bumps.factory;
+        Ok(())
+    }
+
+    pub fn update_factory(ctx: Context<UpdateFactory>, creation_fee: u64, allowlist_enabled: bool, claim_window: i64) -> Result<()> {
+        require!(claim_window >= MIN_CLAIM_WINDOW, ErrorCode::InvalidClaimWindow);
+        let factory = &mut ctx.accounts.factory;
+        factory.treasury = ctx.accounts.treasury.key();
+        factory.creation_fee = creation_fee;
+        factory.allowlist_enabled = allowlist_enabled;
+        factory.claim_window = claim_window; // only applies to markets created from now on
+        Ok(())
+    }
+
+    pub fn set_creator_allowed(ctx: Context<SetCreatorAllowed>, allowed: bool) -> Result<()> {
+        let allowance = &mut ctx.accounts.creator_allowance;
+        allowance.creator = ctx.accounts.creator.key();
+        allowance.allowed = allowed;
+        allowance.bump = ctx.bumps.creator_allowance;
+        Ok(())
+    }
+
+    pub fn init_category_index(ctx: Context<InitCategoryIndex>, category: MarketCategory) -> Result<()> {
+        let index = &mut ctx.accounts.category_index;
+        index.category = category;
+        index.market_ids = vec![];
+        index.bump = ctx.bumps.category_index;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn create_market(
         ctx: Context<CreateMarket>,
         question: String,
         outcomes: Vec<String>,
-        resolution_time: i64,
+        betting_close_time: i64, // last moment bets are accepted
+        event_time: i64, // earliest time the market can be resolved
+        resolution_deadline: i64, // unresolved markets become refundable after this
+        oracle: Option<OracleConfig>,
+        optimistic: Option<OptimisticConfig>,
+        liquidity: Option<u64>, // Some(b) makes this an LMSR market instead of pari-mutuel
+        metadata: MarketMetadata,
+        parent_outcome: Option<u8>, // with a parent_market account, the parent outcome this market is conditional on
+        kind: MarketKind,
+        limits: BetLimits,
+        committee: Option<CommitteeConfig>, // Some to have a resolver committee vote instead of the authority
     ) -> Result<()>{
         let market = &mut ctx.accounts.market; //this is poinnting to the new accoutn which was created during the struct instruction phase
         let clock = Clock::get()?;
         require!(outcomes.len() == 2, ErrorCode::InvalidOutcomeCount); // == 
-        require!(resolution_time > clock.unix_timestamp, ErrorCode::InvalidResolutionTime);
+        require!(betting_close_time > clock.unix_timestamp, ErrorCode::InvalidResolutionTime);
+        require!(event_time >= betting_close_time, ErrorCode::InvalidResolutionTime);
+        require!(resolution_deadline > event_time, ErrorCode::InvalidResolutionTime);
         require!(question.len() <= 200, ErrorCode::QuestionTooLong); // this might be >= 200
+        require!(outcomes.iter().all(|o| o.len() <= metadata::MAX_OUTCOME_LEN), ErrorCode::OutcomeTooLong);
+        metadata.validate()?;
+        kind.validate()?;
+        limits.validate()?;
+        if matches!(kind, MarketKind::Scalar { .. }) {
+            // scalar markets are pari-mutuel LONG/SHORT pools settled by resolve_scalar
+            require!(oracle.is_none() && optimistic.is_none() && committee.is_none() && liquidity.is_none(), ErrorCode::InvalidScalarRange);
+        }
+        if let Some(config) = &oracle {
+            config.validate()?;
+        }
+        if let Some(config) = &optimistic {
+            require!(oracle.is_none(), ErrorCode::InvalidOptimisticConfig); // one resolution source per market
+            config.validate()?;
+        }
+        if committee.is_some() {
+            require!(oracle.is_none() && optimistic.is_none(), ErrorCode::InvalidCommittee);
+        }
+        let committee = committee.map(Committee::new).transpose()?;
+
+        let factory = &mut ctx.accounts.factory;
+        if factory.allowlist_enabled {
+            let allowed = ctx.accounts.creator_allowance.as_ref().is_some_and(|allowance| allowance.allowed);
+            require!(allowed, ErrorCode::CreatorNotAllowed);
+        }
+        require!(ctx.accounts.category_index.category == metadata.category, ErrorCode::CategoryMismatch);
+        if factory.creation_fee > 0 {
+            let cpi_accounts = anchor_lang::system_program::Transfer{
+                from: ctx.accounts.authority.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            anchor_lang::system_program::transfer(cpi_ctx, factory.creation_fee)?;
+        }
+        // "If team A wins the semi, will they win the final?" only resolves once the semi resolved to "team A"
+        market.parent_market = None;
+        market.parent_outcome = 0;
+        if let Some(parent) = &ctx.accounts.parent_market {
+            let parent_outcome = parent_outcome.ok_or(ErrorCode::InvalidParentMarket)?;
+            require!(parent.is_active, ErrorCode::InvalidParentMarket);
+            require!(parent_outcome < parent.outcomes.len() as u8, ErrorCode::InvalidParentMarket);
+            market.parent_market = Some(parent.key());
+            market.parent_outcome = parent_outcome;
+        }
+
+        market.id = factory.market_count;
+        factory.market_count += 1;
+        ctx.accounts.category_index.market_ids.push(market.id);
 
         market.authority = *ctx.accounts.authority.key; // who is providing the ctf
         market.question = question;
         market.outcomes = outcomes;
-        market.resolution_time = resolution_time;
+        market.betting_close_time = betting_close_time;
+        market.event_time = event_time;
+        market.resolution_deadline = resolution_deadline;
+        market.claim_window = factory.claim_window;
+        market.claim_deadline = resolution_deadline.saturating_add(factory.claim_window);
         market.resolved = false;
         market.total_bets = vec![0;2]; // didn;t understand this one
+        market.bettor_count = 0;
+        market.created_at = clock.unix_timestamp;
+        market.exit_fees = 0;
+        market.lp_stake = 0;
+        market.lp_shares = 0;
+        market.paid_out = 0;
+        market.is_active = true;
+        market.refundable = false;
+        market.metadata = metadata;
+        market.kind = kind;
+        market.limits = limits;
+        market.resolved_value = None;
+        market.oracle = oracle;
+        market.optimistic = optimistic;
+        market.committee = committee;
+        market.proposer = None;
+        market.proposed_outcome = None;
+        market.proposed_at = 0;
+        market.disputer = None;
+        market.lmsr = None;
+        market.bump = ctx.bumps.market;
+        market.series = None;
+        market.previous_market = None;
+        market.open_parlay_legs = 0;
+        market.outcome_mints = vec![];
+        market.split_collateral = 0;
+        // SPL collateral (e.g. USDC) when a mint and vault are passed, SOL otherwise
+        market.collateral_mint = ctx.accounts.collateral_mint.as_ref().map(|mint| mint.key());
+        if market.collateral_mint.is_some() {
+            require!(ctx.accounts.vault.is_some() && ctx.accounts.token_program.is_some(), ErrorCode::MissingTokenAccounts);
+            require!(liquidity.is_none(), ErrorCode::SolCollateralOnly);
+        }
+
+        if let Some(b) = liquidity {
+            let lmsr = Lmsr::new(b, market.outcomes.len())?;
+            // creator covers the market maker's worst case loss of b * ln(n)
+            let cpi_accounts = anchor_lang::system_program::Transfer{
+                from: ctx.accounts.authority.to_account_info(),
+                to: market.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            anchor_lang::system_program::transfer(cpi_ctx, lmsr.funding)?;
+            market.lmsr = Some(lmsr);
+        }
+
+        emit!(MarketCreated {
+            market: market.key(),
+            id: market.id,
+            authority: market.authority,
+            question: market.question.clone(),
+            betting_close_time,
+            resolution_deadline,
+        });
+        Ok(())
+    }
+
+    // a template for markets that run back to back, e.g. "BTC up or down, round {round}?" every day
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_series(
+        ctx: Context<CreateSeries>,
+        name: String,
+        question_format: String, // "{round}" is replaced with the round number
+        outcomes: Vec<String>,
+        first_round_at: i64,
+        duration: i64, // betting window of each round; the next round opens when it closes
+        resolution_window: i64, // time after betting close before an unresolved round is refundable
+        oracle: Option<OracleConfig>,
+        metadata: MarketMetadata,
+        limits: BetLimits,
+    ) -> Result<()> {
+        require!(name.len() <= series::MAX_SERIES_NAME_LEN, ErrorCode::InvalidSeries);
+        require!(duration > 0 && resolution_window > 0, ErrorCode::InvalidSeries);
+        require!(outcomes.len() == 2, ErrorCode::InvalidOutcomeCount);
+        require!(question_format.len() <= 180, ErrorCode::QuestionTooLong);
+        require!(series::max_question_len(&question_format) <= 200, ErrorCode::QuestionTooLong); // even at round u64::MAX
+        require!(outcomes.iter().all(|o| o.len() <= metadata::MAX_OUTCOME_LEN), ErrorCode::OutcomeTooLong);
+        metadata.validate()?;
+        limits.validate()?;
+        if let Some(config) = &oracle {
+            config.validate()?;
+        }
+        if ctx.accounts.factory.allowlist_enabled {
+            let allowed = ctx.accounts.creator_allowance.as_ref().is_some_and(|allowance| allowance.allowed);
+            require!(allowed, ErrorCode::CreatorNotAllowed);
+        }
+
+        let series = &mut ctx.accounts.series;
+        series.authority = ctx.accounts.authority.key();
+        series.name = name;
+        series.question_format = question_format;
+        series.outcomes = outcomes;
+        series.duration = duration;
+        series.resolution_window = resolution_window;
+        series.oracle = oracle;
+        series.metadata = metadata;
+        series.limits = limits;
+        series.next_round = 0;
+        series.next_round_at = first_round_at;
+        series.last_market = None;
+        series.bump = ctx.bumps.series;
+        Ok(())
+    }
+
+    // permissionless crank: once the next round has opened, creates its market from the series
+    // template and links it to the previous one. The cranker pays the rent and creation fee.
+    pub fn spawn_next_market(ctx: Context<SpawnNextMarket>) -> Result<()> {
+        let series = &mut ctx.accounts.series;
+        let clock = Clock::get()?;
+
+        require!(clock.unix_timestamp >= series.next_round_at, ErrorCode::RoundNotOpen);
+        if let Some(last_market) = series.last_market {
+            let previous = ctx.accounts.previous_market.as_ref().ok_or(ErrorCode::PreviousMarketMismatch)?;
+            require!(previous.key() == last_market, ErrorCode::PreviousMarketMismatch);
+        }
+        let (round, opens_at) = series::current_round(series.next_round, series.next_round_at, series.duration, clock.unix_timestamp);
+        let betting_close_time = opens_at + series.duration;
+
+        let factory = &mut ctx.accounts.factory;
+        require!(ctx.accounts.category_index.category == series.metadata.category, ErrorCode::CategoryMismatch);
+        if factory.creation_fee > 0 {
+            let cpi_accounts = anchor_lang::system_program::Transfer{
+                from: ctx.accounts.payer.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            anchor_lang::system_program::transfer(cpi_ctx, factory.creation_fee)?;
+        }
+
+        // everything not set here starts zeroed: no bets, no proposals, SOL collateral
+        let market = &mut ctx.accounts.market;
+        market.id = factory.market_count;
+        factory.market_count += 1;
+        ctx.accounts.category_index.market_ids.push(market.id);
+
+        market.authority = series.authority;
+        market.question = series::round_question(&series.question_format, round);
+        market.outcomes = series.outcomes.clone();
+        market.betting_close_time = betting_close_time;
+        market.event_time = betting_close_time;
+        market.resolution_deadline = betting_close_time + series.resolution_window;
+        market.claim_window = factory.claim_window;
+        market.claim_deadline = market.resolution_deadline.saturating_add(factory.claim_window);
+        market.total_bets = vec![0; series.outcomes.len()];
+        market.created_at = clock.unix_timestamp;
         market.is_active = true;
+        market.metadata = series.metadata.clone();
+        market.kind = MarketKind::Binary;
+        market.limits = series.limits;
+        market.oracle = series.oracle;
+        market.bump = ctx.bumps.market;
+        market.series = Some(series.key());
+        market.previous_market = series.last_market;
+
+        series.next_round = round + 1;
+        series.next_round_at = betting_close_time;
+        series.last_market = Some(market.key());
+
+        emit!(MarketCreated {
+            market: market.key(),
+            id: market.id,
+            authority: market.authority,
+            question: market.question.clone(),
+            betting_close_time,
+            resolution_deadline: market.resolution_deadline,
+        });
+        Ok(())
+    }
+
+    // creators can fix up the listing until money is in the market
+    pub fn update_market_metadata(ctx: Context<UpdateMarketMetadata>, metadata: MarketMetadata) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(market.total_bets.iter().all(|total| *total == 0), ErrorCode::MarketHasBets);
+        if let Some(lmsr) = &market.lmsr {
+            require!(lmsr.shares.iter().all(|q| *q == 0), ErrorCode::MarketHasBets);
+        }
+        metadata.validate()?;
+        require!(metadata.category == market.metadata.category, ErrorCode::CategoryMismatch); // the category index already lists this market
 
+        market.metadata = metadata;
         Ok(())
     }
 
-    pub fn place_bet(ctx: Context<PlaceBet>, outcome_index: u8, amount: u64)-> Result<()>{
+    // `proof` is only read for allowlisted markets
+    pub fn place_bet(ctx: Context<PlaceBet>, outcome_index: u8, amount: u64, proof: Vec<[u8; 32]>)-> Result<()>{
         let market = &mut ctx.accounts.market;
         let bettor = &ctx.accounts.bettor;
         let clock = Clock::get()?;
 
         require!(market.is_active, ErrorCode::MarketNotActive);
-        require!(clock.unix_timestamp < market.resolution_time, ErrorCode::BettingClosed); //error[E0609]: no field `unix_timestamp` on type `std::result::Result<anchor_lang::prelude::Clock, anchor_lang::prelude::ProgramError>`
+        require!(market.lmsr.is_none(), ErrorCode::LmsrMarket); // LMSR markets trade through buy_shares
+        require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed); //error[E0609]: no field `unix_timestamp` on type `std::result::Result<anchor_lang::prelude::Clock, anchor_lang::prelude::ProgramError>`
 
         require!(outcome_index < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
         require!(amount>0, ErrorCode::InvalidBetAmount);
+        market.limits.check_allowlist(bettor.key, &proof)?;
+        market.limits.check(&market.total_bets, outcome_index as usize, amount)?;
 
-        let cpi_accounts = anchor_lang::system_program::Transfer{
-            from: ctx.accounts.bettor.to_account_info(),
-            to: market.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+        if market.collateral_mint.is_some() {
+            let (Some(vault), Some(bettor_token), Some(token_program)) =
+                (&ctx.accounts.vault, &ctx.accounts.bettor_token, &ctx.accounts.token_program)
+            else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+            let cpi_accounts = TokenTransfer {
+                from: bettor_token.to_account_info(),
+                to: vault.to_account_info(),
+                authority: bettor.to_account_info(),
+            };
+            token::transfer(CpiContext::new(token_program.to_account_info(), cpi_accounts), amount)?;
+        } else {
+            let cpi_accounts = anchor_lang::system_program::Transfer{
+                from: ctx.accounts.bettor.to_account_info(),
+                to: market.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+        }
         let bet = &mut ctx.accounts.bet;
         bet.bettor = *bettor.key; // this is the users key right which is getting seted up during the instruction phase
         bet.outcome_index = outcome_index;
         bet.amount = amount;
         market.total_bets[outcome_index as usize] += amount;
+        market.bettor_count += 1; // one bet account per bettor
+
+        emit!(BetPlaced {
+            market: market.key(),
+            bettor: bettor.key(),
+            outcome_index,
+            amount,
+            total_bets: market.total_bets.clone(),
+        });
+        Ok(())
+    }
+
+    // changes of heart before betting closes cost an exit fee that stays in the pool for everyone else
+    pub fn withdraw_bet(ctx: Context<WithdrawBet>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &ctx.accounts.bet;
+        let clock = Clock::get()?;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+        require!(market.open_parlay_legs == 0, ErrorCode::ParlayLegsOpen); // open parlays priced their odds off this pool
+
+        let fee = exit::exit_fee(bet.amount, market.created_at, market.betting_close_time, clock.unix_timestamp);
+        market.total_bets[bet.outcome_index as usize] -= bet.amount;
+        market.exit_fees += fee;
+        market.bettor_count -= 1;
+
+        let accounts = &ctx.accounts;
+        pay_from_market(&accounts.market, &accounts.bettor, &accounts.vault, &accounts.bettor_token, &accounts.token_program, bet.amount - fee)?;
+        emit!(BetWithdrawn { market: accounts.market.key(), bettor: accounts.bettor.key(), amount: bet.amount, exit_fee: fee });
+        Ok(()) // the bet account is closed back to the bettor
+    }
+
+    // seeds every outcome with amount / n so thin markets start with sensible odds; the LP gets
+    // shares in the seeded stake, which is paid out like a bet on every outcome
+    pub fn add_liquidity(ctx: Context<AddLiquidity>, amount: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+        require!(market.lmsr.is_none(), ErrorCode::LmsrMarket); // the LMSR is funded at creation instead
+        require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket);
+        require!(market.collateral_mint.is_none(), ErrorCode::SolCollateralOnly);
+        require!(market.open_parlay_legs == 0, ErrorCode::ParlayLegsOpen); // open parlays priced their odds off this pool
+
+        let outcome_count = market.outcomes.len() as u64;
+        let per_outcome = amount / outcome_count;
+        require!(per_outcome > 0, ErrorCode::InvalidBetAmount);
+        let shares = liquidity::shares_for_deposit(per_outcome, market.lp_stake, market.lp_shares)?;
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.provider.to_account_info(),
+            to: market.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, per_outcome * outcome_count)?;
+
+        for total in market.total_bets.iter_mut() {
+            *total += per_outcome;
+        }
+        market.lp_stake += per_outcome;
+        market.lp_shares += shares;
+
+        let lp_position = &mut ctx.accounts.lp_position;
+        lp_position.owner = ctx.accounts.provider.key();
+        lp_position.market = market.key();
+        lp_position.shares += shares;
+        Ok(())
+    }
+
+    // before betting closes (or once refundable) LP shares are worth their stake on every outcome,
+    // plus a share of the exit fees if refundable; after resolution they are worth what the stake won
+    pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, shares: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let lp_position = &mut ctx.accounts.lp_position;
+        let clock = Clock::get()?;
+
+        require!(shares > 0 && shares <= lp_position.shares, ErrorCode::InsufficientShares);
+        let stake = liquidity::stake_for_shares(shares, market.lp_stake, market.lp_shares);
+
+        let amount = if market.resolved {
+            // the LP stake is on every outcome, so it collects from whichever won
+            let settlement = market.settlement()?;
+            let amount = (0..market.outcomes.len() as u8)
+                .map(|outcome| payout::payout(settlement, &market.total_bets, market.exit_fees, outcome, stake))
+                .sum::<u64>();
+            market.paid_out += amount;
+            amount
+        } else {
+            let open = market.is_active && clock.unix_timestamp < market.betting_close_time;
+            require!(open || market.refundable, ErrorCode::LiquidityLocked);
+            // seeding a leg to move its odds and pulling the stake back out once the parlay is placed
+            require!(!open || market.open_parlay_legs == 0, ErrorCode::ParlayLegsOpen);
+            for total in market.total_bets.iter_mut() {
+                *total -= stake;
+            }
+            let fees = if market.refundable { liquidity::fee_share(shares, market.exit_fees, market.lp_shares) } else { 0 };
+            market.exit_fees -= fees;
+            stake * market.outcomes.len() as u64 + fees
+        };
+        market.lp_stake -= stake;
+        market.lp_shares -= shares;
+        lp_position.shares -= shares;
+
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.provider.to_account_info(), amount)?;
+        msg!("Removed {} LP shares for {} lamports", shares, amount);
         Ok(())
     }
 
@@ -59,113 +505,1816 @@ pub mod prediction_market {
         let clock = Clock::get()?;
 
         require!(market.authority==*ctx.accounts.authority.key, ErrorCode::Unauthorized);
+        require!(market.oracle.is_none(), ErrorCode::OracleMarket); // price markets go through resolve_with_oracle
+        require!(market.optimistic.is_none(), ErrorCode::OptimisticMarket);
+        require!(market.committee.is_none(), ErrorCode::CommitteeMarket); // committee markets go through vote_resolution
+        require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket);
         require!(market.is_active, ErrorCode::MarketNotActive);
-        require!(clock.unix_timestamp >= market.resolution_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp >= market.event_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp < market.resolution_deadline, ErrorCode::ResolutionDeadlinePassed);
         require!(winning_outcome < market.outcomes.len() as u8, ErrorCode::InvalidOutcome); // i didn't understnad this logic, what does he mean
+        check_parent_condition(market, &ctx.accounts.parent_market)?;
         
         market.resolved = true;
         market.is_active = false;
+        market.open_claim_window(clock.unix_timestamp);
         market.winning_outcome = Some(winning_outcome); // y are we using some here because winning out_come is not a result enum right then y are we using
+        emit_resolved(market)
+    }
+
+    // committee members vote one at a time; the market resolves once `threshold` agree, and falls
+    // back to refunds if the votes split so that no outcome can get there any more
+    pub fn vote_resolution(ctx: Context<VoteResolution>, outcome: u8) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp >= market.event_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp < market.resolution_deadline, ErrorCode::ResolutionDeadlinePassed);
+        require!(outcome < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+        check_parent_condition(market, &ctx.accounts.parent_market)?;
+
+        let outcome_count = market.outcomes.len();
+        let market_key = market.key();
+        let member = ctx.accounts.member.key();
+        let committee = market.committee.as_mut().ok_or(ErrorCode::NotCommitteeMarket)?;
+        let tally = committee.vote(&member, outcome, outcome_count)?;
+        emit!(CommitteeVoted {
+            market: market_key,
+            member,
+            outcome,
+            votes_for_outcome: committee.votes_for(outcome) as u8,
+            threshold: committee.threshold,
+        });
+
+        match tally {
+            Tally::Pending => Ok(()),
+            Tally::Agreed(winning_outcome) => {
+                market.resolved = true;
+                market.is_active = false;
+                market.open_claim_window(clock.unix_timestamp);
+                market.winning_outcome = Some(winning_outcome);
+                emit_resolved(market)
+            }
+            Tally::Deadlocked => {
+                market.is_active = false;
+                market.refundable = true;
+                market.open_claim_window(clock.unix_timestamp);
+                emit!(CommitteeDeadlocked { market: market_key });
+                Ok(())
+            }
+        }
+    }
+
+    // settles a scalar market at `value`; LONG and SHORT split the pool by where it lands in [min, max]
+    pub fn resolve_scalar(ctx: Context<ResolveMarket>, value: i64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        require!(matches!(market.kind, MarketKind::Scalar { .. }), ErrorCode::NotScalarMarket);
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp >= market.event_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp < market.resolution_deadline, ErrorCode::ResolutionDeadlinePassed);
+        check_parent_condition(market, &ctx.accounts.parent_market)?;
+
+        market.resolved = true;
+        market.is_active = false;
+        market.open_claim_window(clock.unix_timestamp);
+        market.resolved_value = Some(value);
+        emit_resolved(market)
+    }
+
+    // permissionless: anyone can crank a price market once the feed has a fresh enough price
+    pub fn resolve_with_oracle(ctx: Context<ResolveWithOracle>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.oracle.ok_or(ErrorCode::NotOracleMarket)?;
+        require!(ctx.accounts.price_feed.key() == config.feed, ErrorCode::OracleFeedMismatch);
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp >= market.event_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp < market.resolution_deadline, ErrorCode::ResolutionDeadlinePassed);
+
+        check_parent_condition(market, &ctx.accounts.parent_market)?;
+
+        let feed = PriceFeed::parse(&ctx.accounts.price_feed.try_borrow_data()?)?;
+        let winning_outcome = config.resolve(&feed, market.event_time, clock.unix_timestamp)?;
+
+        market.resolved = true;
+        market.is_active = false;
+        market.open_claim_window(clock.unix_timestamp);
+        market.winning_outcome = Some(winning_outcome);
+        msg!("Oracle price {} (expo {}) resolved outcome {}", feed.price, feed.expo, winning_outcome);
+        emit_resolved(market)
+    }
+
+    pub fn propose_outcome(ctx: Context<ProposeOutcome>, outcome: u8) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.optimistic.ok_or(ErrorCode::NotOptimisticMarket)?;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp >= market.event_time, ErrorCode::MarketNotReslvable);
+        require!(clock.unix_timestamp < market.resolution_deadline, ErrorCode::ResolutionDeadlinePassed);
+        require!(market.proposed_outcome.is_none(), ErrorCode::ProposalExists);
+        require!(outcome < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+        check_parent_condition(market, &ctx.accounts.parent_market)?;
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.proposer.to_account_info(),
+            to: market.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, config.bond)?;
+
+        market.proposer = Some(ctx.accounts.proposer.key());
+        market.proposed_outcome = Some(outcome);
+        market.proposed_at = clock.unix_timestamp;
         Ok(())
     }
 
-    pub fn claim_payout(ctx: Context<ClaimPayout>)->Result<()>{
+    pub fn dispute_outcome(ctx: Context<DisputeOutcome>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.optimistic.ok_or(ErrorCode::NotOptimisticMarket)?;
+        require!(market.proposed_outcome.is_some(), ErrorCode::NoProposal);
+        require!(market.disputer.is_none(), ErrorCode::AlreadyDisputed);
+        require!(clock.unix_timestamp < config.challenge_ends_at(market.proposed_at), ErrorCode::ChallengeWindowClosed);
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.disputer.to_account_info(),
+            to: market.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, config.bond)?;
+
+        market.disputer = Some(ctx.accounts.disputer.key());
+        Ok(())
+    }
+
+    // permissionless: an unchallenged proposal becomes the result once the window has passed
+    pub fn finalize_outcome(ctx: Context<FinalizeOutcome>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.optimistic.ok_or(ErrorCode::NotOptimisticMarket)?;
+        require!(market.is_active && !market.resolved, ErrorCode::MarketNotActive);
+        let proposed_outcome = market.proposed_outcome.ok_or(ErrorCode::NoProposal)?;
+        require!(market.disputer.is_none(), ErrorCode::AlreadyDisputed);
+        require!(market.proposer == Some(ctx.accounts.proposer.key()), ErrorCode::ProposerMismatch);
+        require!(clock.unix_timestamp >= config.challenge_ends_at(market.proposed_at), ErrorCode::ChallengeWindowOpen);
+
+        market.resolved = true;
+        market.is_active = false;
+        market.open_claim_window(clock.unix_timestamp);
+        market.winning_outcome = Some(proposed_outcome);
+        market.proposer = None;
+        market.proposed_outcome = None;
+        market.proposed_at = 0;
+
+        // bond goes back to the proposer, once
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.proposer.to_account_info(), config.bond)?;
+        emit_resolved(market)
+    }
+
+    // the arbiter rules on a disputed proposal; the side it agrees with gets both bonds
+    pub fn arbitrate(ctx: Context<Arbitrate>, outcome: u8) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.optimistic.ok_or(ErrorCode::NotOptimisticMarket)?;
+        require!(config.arbiter == ctx.accounts.arbiter.key(), ErrorCode::Unauthorized);
+        let proposed_outcome = market.proposed_outcome.ok_or(ErrorCode::NoProposal)?;
+        let disputer = market.disputer.ok_or(ErrorCode::NotDisputed)?;
+        let proposer = market.proposer.ok_or(ErrorCode::NoProposal)?;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(outcome < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+        require!(ctx.accounts.proposer.key() == proposer, ErrorCode::ProposerMismatch);
+        require!(ctx.accounts.disputer.key() == disputer, ErrorCode::DisputerMismatch);
+        require!(clock.unix_timestamp < config.arbitration_ends_at(market.proposed_at), ErrorCode::ArbitrationClosed);
+
+        market.resolved = true;
+        market.is_active = false;
+        market.open_claim_window(clock.unix_timestamp);
+        market.winning_outcome = Some(outcome);
+
+        let winner = if outcome == proposed_outcome {
+            ctx.accounts.proposer.to_account_info()
+        } else {
+            ctx.accounts.disputer.to_account_info()
+        };
+        transfer_lamports(&market.to_account_info(), &winner, config.bond * 2)?;
+        emit_resolved(market)
+    }
+
+    // permissionless: a dispute the arbiter never ruled on sends the market to refunds once the
+    // arbitration window is over, and both sides get their bonds back
+    pub fn expire_dispute(ctx: Context<ExpireDispute>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let config = market.optimistic.ok_or(ErrorCode::NotOptimisticMarket)?;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        let disputer = market.disputer.ok_or(ErrorCode::NotDisputed)?;
+        let proposer = market.proposer.ok_or(ErrorCode::NoProposal)?;
+        require!(ctx.accounts.proposer.key() == proposer, ErrorCode::ProposerMismatch);
+        require!(ctx.accounts.disputer.key() == disputer, ErrorCode::DisputerMismatch);
+        require!(clock.unix_timestamp >= config.arbitration_ends_at(market.proposed_at), ErrorCode::ArbitrationWindowOpen);
+
+        market.is_active = false;
+        market.refundable = true;
+        market.open_claim_window(clock.unix_timestamp);
+        market.proposer = None;
+        market.proposed_outcome = None;
+        market.proposed_at = 0;
+        market.disputer = None;
+
+        let market_info = market.to_account_info();
+        transfer_lamports(&market_info, &ctx.accounts.proposer.to_account_info(), config.bond)?;
+        transfer_lamports(&market_info, &ctx.accounts.disputer.to_account_info(), config.bond)
+    }
+
+    pub fn buy_shares(ctx: Context<BuyShares>, outcome_index: u8, amount: u64, max_cost: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+        require!(outcome_index < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+        require!(amount > 0, ErrorCode::InvalidBetAmount);
+        let lmsr = market.lmsr.as_mut().ok_or(ErrorCode::NotLmsrMarket)?;
+
+        let cost = lmsr.buy_cost(outcome_index as usize, amount)?;
+        require!(cost <= max_cost, ErrorCode::SlippageExceeded);
+        lmsr.shares[outcome_index as usize] += amount;
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.trader.to_account_info(),
+            to: market.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, cost)?;
+
+        let position = &mut ctx.accounts.position;
+        position.open(ctx.accounts.trader.key(), market)?;
+        position.shares[outcome_index as usize] += amount;
+        msg!("Bought {} shares of outcome {} for {} lamports", amount, outcome_index, cost);
+        Ok(())
+    }
+
+    pub fn sell_shares(ctx: Context<SellShares>, outcome_index: u8, amount: u64, min_refund: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        let clock = Clock::get()?;
+
+        // a refundable market lets traders exit at the curve price since no outcome will ever win
+        require!(market.is_active || market.refundable, ErrorCode::MarketNotActive);
+        require!(market.refundable || clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+        require!(outcome_index < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+        require!(amount > 0, ErrorCode::InvalidBetAmount);
+        require!(position.shares[outcome_index as usize] >= amount, ErrorCode::InsufficientShares);
+        let lmsr = market.lmsr.as_mut().ok_or(ErrorCode::NotLmsrMarket)?;
+
+        let refund = lmsr.sell_refund(outcome_index as usize, amount)?;
+        require!(refund >= min_refund, ErrorCode::SlippageExceeded);
+        lmsr.shares[outcome_index as usize] -= amount;
+        position.shares[outcome_index as usize] -= amount;
+
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.trader.to_account_info(), refund)?;
+        msg!("Sold {} shares of outcome {} for {} lamports", amount, outcome_index, refund);
+        Ok(())
+    }
+
+    // each winning share redeems for 1 lamport once the market is resolved
+    pub fn redeem_shares(ctx: Context<RedeemShares>) -> Result<()> {
         let market = &ctx.accounts.market;
-        let bet = &ctx.accounts.bet;
-        let bettor = &ctx.accounts.bettor;
-        
+        let position = &mut ctx.accounts.position;
+
+        require!(market.lmsr.is_some(), ErrorCode::NotLmsrMarket);
         require!(market.resolved, ErrorCode::MarketNotResolved);
-        require!(market.winning_outcome==Some(bet.outcome_index), ErrorCode::NotWinningBet);
-        let total_winning_bets = market.total_bets[bet.outcome_index as usize];
-        let total_pool = market.total_bets.iter().sum::<u64>(); //this is summing all the elements of an array
-        let payout = if total_winning_bets > 0 {
-            (bet.amount as u128 * total_pool as u128 / total_winning_bets as u128) as u64
-        }else{
-            0 //error
-        };
-        require!(payout>0, ErrorCode::NoPayout);
+        let winning_outcome = market.winning_outcome.ok_or(ErrorCode::MarketNotResolved)? as usize;
 
-        **ctx.accounts.market.to_account_info().try_borrow_mut_lamports()? -= payout; // how is the contract getting authority to transfer the amount 
-        **ctx.accounts.bettor.to_account_info().try_borrow_mut_lamports()?+=payout; // so the system program will transfer the amiount or the authority of the market willl transfer the amount
+        let payout = position.shares[winning_outcome];
+        require!(payout > 0, ErrorCode::NoPayout);
+        position.shares[winning_outcome] = 0;
 
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.trader.to_account_info(), payout)?;
         Ok(())
     }
-}
 
-#[derive(Accounts)] // creating a market account like eg ipl teams csk, rcb etc
-#[instruction(question:String, outcomes: Vec<String>, resolution_time: i64)] // i didn't understand this
-pub struct CreateMarket<'info> {
-    #[account(
-        init,
-        payer=authority,
-        space=8 + 32 + 4 + 200 + 4 + 2 * 50 + 8 + 1 + 4 + 2 * 8 + 1 + 8,
-        seeds = [b"market", authority.key().as_ref(), question.as_bytes()], //y are we adding this line
-        bump
-    )]
-    pub market: Account<'info, Market>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>
-}
+    // the book trades the outcome's SPL token: asks are escrowed in a token account the market
+    // owns, so the market's outcome mints have to exist first
+    pub fn init_order_book(ctx: Context<InitOrderBook>, outcome: u8) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(market.collateral_mint.is_none(), ErrorCode::SolCollateralOnly);
+        require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket);
+        let mint = market.outcome_mints.get(outcome as usize).ok_or(ErrorCode::OutcomeMintsMissing)?;
+        require!(*mint == ctx.accounts.outcome_mint.key(), ErrorCode::OrderBookMismatch);
 
-#[derive(Accounts)] // an accouint which will have the details about which team / market did the user bet on
-#[instruction(outcome_index:u8, amount: u64)] // the arguments the user will provide
-pub struct PlaceBet<'info>{
-    #[account(mut, has_one = authority)] // //authority not in scope so this is checking the market account whether it is from the correct authority or not
-    pub market: Account<'info, Market>, // the market account to bet on
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(
-        init,
-        payer = bettor,
-        space = 8+32+1+8,
-        seeds = [b"bet", market.key().as_ref(), bettor.key().as_ref()],
-        bump
-    )] // this macro is used to create a required bet account
-    pub bet: Account<'info, Bet>,
-    #[account(mut)]
-    pub bettor: Signer<'info>, 
-    pub system_program: Program<'info, System>,
-}
+        let mut book = ctx.accounts.order_book.load_init()?;
+        book.market = market.key();
+        book.mint = *mint;
+        book.outcome = outcome;
+        Ok(())
+    }
 
+    // bids lock price * quantity lamports in the market, asks lock the tokens in the book's escrow
+    pub fn place_order(ctx: Context<PlaceOrder>, side: Side, price: u64, quantity: u64) -> Result<()> {
+        let market = &ctx.accounts.market;
+        let clock = Clock::get()?;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+        // the slabs are bounded, so dust orders can't be allowed to fill them up
+        require!(orderbook::order_value(quantity, price)? >= orderbook::MIN_ORDER_VALUE, ErrorCode::OrderTooSmall);
 
-#[derive(Accounts)]
-#[instruction(winning_outcome:u8)]
-pub struct ResolveMarket<'info> {
-    #[account(mut, has_one=authority)]
-    pub market: Account<'info, Market>,
-    pub authority: Signer<'info>,
-}
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        match side {
+            Side::Bid => {
+                let cpi_accounts = anchor_lang::system_program::Transfer{
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: market.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+                anchor_lang::system_program::transfer(cpi_ctx, orderbook::order_value(quantity, price)?)?;
+            }
+            Side::Ask => {
+                let cpi_accounts = TokenTransfer {
+                    from: ctx.accounts.owner_token.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                };
+                token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), quantity)?;
+            }
+        }
 
-#[derive(Accounts)]
-pub struct ClaimPayout<'info>{
-    #[account(mut, has_one = authority)] //authority not in scope
-    pub market: Account<'info, Market>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub bet: Account<'info, Bet>,
-    #[account(mut)] // Mutable bettor account to receive payout.
-    pub bettor: Signer<'info>, // Bettor must sign.
-}
+        let seq = book.insert(side, ctx.accounts.owner.key(), price, quantity)?;
+        msg!("Order {} placed: {:?} {} @ {}", seq, side, quantity, price);
+        Ok(())
+    }
 
-#[account]
-pub struct Bet{
-    pub bettor: Pubkey,
-    pub outcome_index: u8,
-    pub amount: u64
-}
+    // pulls a resting order and hands back whatever it still had locked
+    pub fn cancel_order(ctx: Context<CancelOrder>, side: Side, seq: u64) -> Result<()> {
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        let index = book.find(side, seq).ok_or(ErrorCode::OrderNotFound)?;
+        require!(book.orders(side)[index].owner == ctx.accounts.owner.key(), ErrorCode::Unauthorized);
+        let order = book.remove(side, index);
 
-#[account]
-pub struct Market{
-    pub authority: Pubkey,
-    pub question: String,
-    pub outcomes: Vec<String>,
-    pub resolution_time: i64,
-    pub resolved: bool,
-    pub winning_outcome: Option<u8> ,// this one is doubt option is given because at the start is None after the resolution time the winner index is decided so we wait until then
-    pub total_bets: Vec<u64>,
-    pub is_active: bool
+        match side {
+            Side::Bid => {
+                let refund = orderbook::order_value(order.quantity, order.price)?;
+                transfer_lamports(&ctx.accounts.market.to_account_info(), &ctx.accounts.owner.to_account_info(), refund)?;
+            }
+            Side::Ask => {
+                let market = &ctx.accounts.market;
+                let id = market.id.to_le_bytes();
+                let bump = [market.bump];
+                let signer_seeds: &[&[u8]] = &[b"market", &id, &bump];
+                let signers = &[signer_seeds];
+                let cpi_accounts = TokenTransfer {
+                    from: ctx.accounts.escrow.to_account_info(),
+                    to: ctx.accounts.owner_token.to_account_info(),
+                    authority: market.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signers);
+                token::transfer(cpi_ctx, order.quantity)?;
+            }
+        }
+        Ok(())
+    }
+
+    // permissionless crank: crosses the best bid and ask up to `limit` times, filling at the
+    // older (maker) order's price. remaining_accounts must hold each buyer's outcome token
+    // account (the ATA place_order created) and wallet, and each seller's wallet, so fills
+    // deliver the tokens and settle in collateral right away. A bid whose ATA has since been
+    // closed can't take delivery, so it is cancelled and refunded instead of stalling the book.
+    pub fn match_orders<'info>(ctx: Context<'_, '_, 'info, 'info, MatchOrders<'info>>, limit: u8) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+
+        let market_info = market.to_account_info();
+        let id = market.id.to_le_bytes();
+        let bump = [market.bump];
+        let signer_seeds: &[&[u8]] = &[b"market", &id, &bump];
+        let signers = &[signer_seeds];
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        let mut fills = 0;
+        while fills < limit {
+            let (Some(bid_index), Some(ask_index)) = (book.best_bid(), book.best_ask()) else {
+                break;
+            };
+            let (bid, ask) = (book.bids[bid_index], book.asks[ask_index]);
+            if bid.price < ask.price {
+                break;
+            }
+
+            let quantity = bid.quantity.min(ask.quantity);
+            let price = if bid.seq < ask.seq { bid.price } else { ask.price };
+            let proceeds = orderbook::order_value(quantity, price)?;
+            let price_improvement = orderbook::order_value(quantity, bid.price)? - proceeds;
+
+            let buyer_wallet = find_wallet(ctx.remaining_accounts, bid.owner)?;
+            let seller_wallet = find_wallet(ctx.remaining_accounts, ask.owner)?;
+            let buyer_token = find_token_account(ctx.remaining_accounts, bid.owner, book.mint)?;
+            if buyer_token.owner != &token::ID || buyer_token.data_is_empty() {
+                transfer_lamports(&market_info, buyer_wallet, orderbook::order_value(bid.quantity, bid.price)?)?;
+                book.remove(Side::Bid, bid_index);
+                msg!("Cancelled bid {}: its token account is closed", bid.seq);
+                fills += 1;
+                continue;
+            }
+            let cpi_accounts = TokenTransfer {
+                from: ctx.accounts.escrow.to_account_info(),
+                to: buyer_token.clone(),
+                authority: market_info.clone(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signers);
+            token::transfer(cpi_ctx, quantity)?;
+
+            transfer_lamports(&market_info, seller_wallet, proceeds)?;
+            if price_improvement > 0 {
+                transfer_lamports(&market_info, buyer_wallet, price_improvement)?;
+            }
+
+            book.orders_mut(Side::Bid)[bid_index].quantity -= quantity;
+            book.orders_mut(Side::Ask)[ask_index].quantity -= quantity;
+            if book.bids[bid_index].quantity == 0 {
+                book.remove(Side::Bid, bid_index);
+            }
+            if book.asks[ask_index].quantity == 0 {
+                book.remove(Side::Ask, ask_index);
+            }
+            msg!("Filled {} @ {} between {} and {}", quantity, price, bid.seq, ask.seq);
+            fills += 1;
+        }
+        Ok(())
+    }
+
+    // one SPL mint per outcome, with the market PDA as mint authority
+    pub fn init_outcome_mints(ctx: Context<InitOutcomeMints>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(market.outcome_mints.is_empty(), ErrorCode::OutcomeMintsExist);
+        require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket); // redeem pays the winning outcome's token
+        market.outcome_mints = vec![ctx.accounts.outcome_mint_0.key(), ctx.accounts.outcome_mint_1.key()];
+        Ok(())
+    }
+
+    // lock `amount` lamports of collateral and mint one full set of outcome tokens
+    pub fn split(ctx: Context<SplitMerge>, amount: u64) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        require!(market.collateral_mint.is_none(), ErrorCode::SolCollateralOnly);
+        require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket);
+        require!(amount > 0, ErrorCode::InvalidBetAmount);
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.user.to_account_info(),
+            to: market.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+
+        let id = market.id.to_le_bytes();
+        let bump = [market.bump];
+        let signer_seeds: &[&[u8]] = &[b"market", &id, &bump];
+        let signers = &[signer_seeds];
+        for (mint, to) in [
+            (&ctx.accounts.outcome_mint_0, &ctx.accounts.user_token_0),
+            (&ctx.accounts.outcome_mint_1, &ctx.accounts.user_token_1),
+        ] {
+            let cpi_accounts = MintTo {
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: market.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signers);
+            token::mint_to(cpi_ctx, amount)?;
+        }
+
+        ctx.accounts.market.split_collateral += amount;
+        Ok(())
+    }
+
+    // burn one full set of outcome tokens and get the collateral back
+    pub fn merge(ctx: Context<SplitMerge>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidBetAmount);
+
+        for (mint, from) in [
+            (&ctx.accounts.outcome_mint_0, &ctx.accounts.user_token_0),
+            (&ctx.accounts.outcome_mint_1, &ctx.accounts.user_token_1),
+        ] {
+            let cpi_accounts = Burn {
+                mint: mint.to_account_info(),
+                from: from.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            token::burn(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+        }
+
+        let market = &mut ctx.accounts.market;
+        market.split_collateral = market.split_collateral.checked_sub(amount).ok_or(ErrorCode::InsufficientShares)?;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), amount)?;
+        Ok(())
+    }
+
+    // burn winning outcome tokens for 1 lamport each once the market is resolved
+    pub fn redeem(ctx: Context<Redeem>, amount: u64) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        let winning_outcome = market.winning_outcome.ok_or(ErrorCode::MarketNotResolved)? as usize;
+        let winning_mint = market.outcome_mints.get(winning_outcome).ok_or(ErrorCode::OutcomeMintsMissing)?;
+        require!(*winning_mint == ctx.accounts.winning_mint.key(), ErrorCode::NotWinningBet);
+        require!(amount > 0, ErrorCode::InvalidBetAmount);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.winning_mint.to_account_info(),
+            from: ctx.accounts.user_token.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token::burn(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+
+        let market = &mut ctx.accounts.market;
+        market.split_collateral = market.split_collateral.checked_sub(amount).ok_or(ErrorCode::InsufficientShares)?;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), amount)?;
+        Ok(())
+    }
+
+    // no outcome wins a refundable market, so every outcome token is worth an equal share of the
+    // lamport its set locked. Full sets can still be merged back at par first.
+    pub fn refund_tokens(ctx: Context<RefundTokens>, amount: u64) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(market.refundable, ErrorCode::MarketNotRefundable);
+        require!(market.outcome_mints.contains(&ctx.accounts.outcome_mint.key()), ErrorCode::OutcomeMintsMissing);
+        let refund = amount / market.outcome_mints.len() as u64;
+        require!(refund > 0, ErrorCode::InvalidBetAmount);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.outcome_mint.to_account_info(),
+            from: ctx.accounts.user_token.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token::burn(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+
+        let market = &mut ctx.accounts.market;
+        market.split_collateral = market.split_collateral.checked_sub(refund).ok_or(ErrorCode::InsufficientShares)?;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), refund)?;
+        Ok(())
+    }
+
+    // permissionless: a market nobody resolved before its deadline, or whose parent went
+    // the other way, falls back to refunds
+    pub fn mark_refundable(ctx: Context<MarkRefundable>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.is_active, ErrorCode::MarketNotActive);
+        let parent_failed = match market.parent_market {
+            Some(parent_key) => {
+                let parent = ctx.accounts.parent_market.as_ref().ok_or(ErrorCode::ParentMarketMismatch)?;
+                require!(parent.key() == parent_key, ErrorCode::ParentMarketMismatch);
+                parent.refundable || (parent.resolved && parent.winning_outcome != Some(market.parent_outcome))
+            }
+            None => false,
+        };
+        require!(parent_failed || clock.unix_timestamp >= market.resolution_deadline, ErrorCode::ResolutionDeadlineNotPassed);
+        require!(market.proposed_outcome.is_none(), ErrorCode::ProposalExists); // pending proposals settle through finalize_outcome / arbitrate / expire_dispute
+
+        market.is_active = false;
+        market.refundable = true;
+        market.open_claim_window(clock.unix_timestamp);
+        Ok(())
+    }
+
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &ctx.accounts.bet;
+
+        require!(market.refundable, ErrorCode::MarketNotRefundable);
+        market.total_bets[bet.outcome_index as usize] -= bet.amount;
+
+        let accounts = &ctx.accounts;
+        pay_from_market(&accounts.market, &accounts.bettor, &accounts.vault, &accounts.bettor_token, &accounts.token_program, accounts.bet.amount)?;
+        emit!(RefundClaimed { market: accounts.market.key(), bettor: accounts.bettor.key(), amount: accounts.bet.amount });
+        Ok(()) // the bet account is closed back to the bettor, so it can only be refunded once
+    }
+
+    pub fn claim_payout(ctx: Context<ClaimPayout>)->Result<()>{
+        let market = &ctx.accounts.market;
+        let bet = &ctx.accounts.bet;
+        
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        require!(!bet.claimed, ErrorCode::AlreadyClaimed);
+        if market.kind == MarketKind::Binary {
+            require!(market.winning_outcome==Some(bet.outcome_index), ErrorCode::NotWinningBet);
+        }
+        let payout = bet_payout(market, bet)?;
+        require!(payout>0, ErrorCode::NoPayout);
+
+        let accounts = &ctx.accounts;
+        pay_from_market(&accounts.market, &accounts.bettor, &accounts.vault, &accounts.bettor_token, &accounts.token_program, payout)?;
+        emit!(PayoutClaimed { market: accounts.market.key(), bettor: accounts.bettor.key(), amount: payout });
+        ctx.accounts.bet.claimed = true;
+        ctx.accounts.market.paid_out += payout;
+
+        Ok(())
+    }
+
+    // claims every winning bet passed as (market, bet) pairs in remaining_accounts in one go.
+    // Losing, already claimed and unresolved bets are skipped instead of failing the batch.
+    // SPL collateral markets need their vault accounts, so passing one fails the batch and
+    // those bets go through claim_payout.
+    pub fn claim_many<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimMany<'info>>) -> Result<()> {
+        let bettor = &ctx.accounts.bettor;
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(pairs.remainder().is_empty(), ErrorCode::InvalidClaimAccounts);
+
+        let mut total = 0u64;
+        let mut claimed = 0u32;
+        for pair in pairs {
+            let (market_info, bet_info) = (&pair[0], &pair[1]);
+            let mut market = Account::<Market>::try_from(market_info)?;
+            require!(market.collateral_mint.is_none(), ErrorCode::SolCollateralOnly);
+            let mut bet = Account::<Bet>::try_from(bet_info)?;
+            let (bet_address, _) = Pubkey::find_program_address(&[b"bet", market_info.key.as_ref(), bettor.key.as_ref()], &crate::ID);
+            require!(bet_info.key() == bet_address, ErrorCode::InvalidClaimAccounts);
+            require!(bet.bettor == bettor.key(), ErrorCode::Unauthorized);
+
+            if !market.resolved || bet.claimed {
+                continue;
+            }
+            let payout = bet_payout(&market, &bet)?;
+            if payout == 0 {
+                continue;
+            }
+
+            transfer_lamports(market_info, &bettor.to_account_info(), payout)?;
+            bet.claimed = true;
+            bet.exit(&crate::ID)?;
+            market.paid_out += payout;
+            market.exit(&crate::ID)?;
+            emit!(PayoutClaimed { market: market.key(), bettor: bettor.key(), amount: payout });
+            total += payout;
+            claimed += 1;
+        }
+        msg!("Claimed {} lamports from {} bets", total, claimed);
+        Ok(())
+    }
+
+    // permissionless once the claim deadline has passed: whatever is left in a settled market
+    // (unclaimed winnings, open order escrow, rounding dust) goes to the treasury, and the
+    // market account is closed with its rent returned to the authority. Nobody won a refundable
+    // market, so its exit fees and LMSR subsidy go back to the creator instead.
+    pub fn finalize_market(ctx: Context<FinalizeMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.resolved || market.refundable, ErrorCode::MarketNotSettled);
+        require!(clock.unix_timestamp >= market.claim_deadline, ErrorCode::ClaimPeriodOpen);
+        require!(market.open_parlay_legs == 0, ErrorCode::ParlayLegsOpen); // settle_parlay still reads this market
+
+        let creator_share = if market.refundable {
+            market.exit_fees + market.lmsr.as_ref().map_or(0, |lmsr| lmsr.funding)
+        } else {
+            0
+        };
+        let mut returned = 0;
+        let mut swept_tokens = 0;
+        if market.collateral_mint.is_some() {
+            let (Some(vault), Some(treasury_token), Some(token_program)) =
+                (&ctx.accounts.vault, &ctx.accounts.treasury_token, &ctx.accounts.token_program)
+            else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+            let id = market.id.to_le_bytes();
+            let bump = [market.bump];
+            let signer_seeds: &[&[u8]] = &[b"market", &id, &bump];
+            let signers = &[signer_seeds];
+            let transfer_from_vault = |to, amount| {
+                let cpi_accounts = TokenTransfer {
+                    from: vault.to_account_info(),
+                    to,
+                    authority: market.to_account_info(),
+                };
+                token::transfer(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signers), amount)
+            };
+
+            returned = creator_share.min(vault.amount);
+            if returned > 0 {
+                let authority_token = ctx.accounts.authority_token.as_ref().ok_or(ErrorCode::MissingTokenAccounts)?;
+                transfer_from_vault(authority_token.to_account_info(), returned)?;
+            }
+            swept_tokens = vault.amount - returned;
+            transfer_from_vault(treasury_token.to_account_info(), swept_tokens)?;
+            let cpi_accounts = CloseAccount {
+                account: vault.to_account_info(),
+                destination: ctx.accounts.authority.to_account_info(),
+                authority: market.to_account_info(),
+            };
+            token::close_account(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signers))?;
+        }
+
+        let market_info = market.to_account_info();
+        let rent = Rent::get()?.minimum_balance(market_info.data_len());
+        let mut swept = market_info.lamports().saturating_sub(rent);
+        if market.collateral_mint.is_none() {
+            returned = creator_share.min(swept);
+            swept -= returned;
+            transfer_lamports(&market_info, &ctx.accounts.authority.to_account_info(), returned)?;
+        }
+        transfer_lamports(&market_info, &ctx.accounts.treasury.to_account_info(), swept)?;
+
+        emit!(MarketFinalized { market: market.key(), swept, swept_tokens, returned_to_creator: returned });
+        Ok(()) // the market account itself is closed to the authority
+    }
+
+    // the house backs fixed-odds parlays: it takes the stakes and pays the winners, so the factory
+    // admin funds it up front and can only withdraw what open parlays have not reserved
+    pub fn init_parlay_house(ctx: Context<InitParlayHouse>) -> Result<()> {
+        let house = &mut ctx.accounts.house;
+        house.admin = ctx.accounts.admin.key();
+        house.reserved = 0;
+        house.parlay_count = 0;
+        house.bump = ctx.bumps.house;
+        Ok(())
+    }
+
+    pub fn fund_parlay_house(ctx: Context<FundParlayHouse>, amount: u64) -> Result<()> {
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.funder.to_account_info(),
+            to: ctx.accounts.house.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, amount)
+    }
+
+    pub fn withdraw_parlay_house(ctx: Context<WithdrawParlayHouse>, amount: u64) -> Result<()> {
+        let house = &ctx.accounts.house;
+        require!(amount <= house_free_balance(house)?, ErrorCode::HouseUnderfunded);
+        transfer_lamports(&house.to_account_info(), &ctx.accounts.admin.to_account_info(), amount)
+    }
+
+    // one bet on several markets at once: the legs' markets are passed (writable) in
+    // remaining_accounts, in the same order as `outcomes`. Each leg's odds are the capped pool odds
+    // at placement and stay fixed; the house reserves the whole potential payout until the parlay
+    // settles. While a parlay is open its markets lock withdraw_bet and LP deposits and withdrawals,
+    // so nobody can inflate a leg's odds and pull the money back out afterwards; the minimum stake
+    // keeps that lock from being had for a lamport.
+    pub fn place_parlay<'info>(ctx: Context<'_, '_, 'info, 'info, PlaceParlay<'info>>, outcomes: Vec<u8>, stake: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(stake >= parlay::MIN_STAKE, ErrorCode::ParlayStakeTooSmall);
+        require!(outcomes.len() >= 2 && outcomes.len() <= parlay::MAX_LEGS, ErrorCode::InvalidParlayLegs);
+        require!(ctx.remaining_accounts.len() == outcomes.len(), ErrorCode::InvalidParlayLegs);
+
+        let mut legs = Vec::with_capacity(outcomes.len());
+        for (market_info, outcome) in ctx.remaining_accounts.iter().zip(outcomes) {
+            let mut market = Account::<Market>::try_from(market_info)?;
+            require!(legs.iter().all(|leg: &ParlayLeg| leg.market != market.key()), ErrorCode::InvalidParlayLegs);
+            require!(market.is_active, ErrorCode::MarketNotActive);
+            require!(clock.unix_timestamp < market.betting_close_time, ErrorCode::BettingClosed);
+            require!(market.lmsr.is_none(), ErrorCode::LmsrMarket);
+            require!(market.kind == MarketKind::Binary, ErrorCode::ScalarMarket);
+            require!(outcome < market.outcomes.len() as u8, ErrorCode::InvalidOutcome);
+            legs.push(ParlayLeg { market: market.key(), outcome, odds: parlay::leg_odds(&market.total_bets, outcome)? });
+            market.open_parlay_legs += 1;
+            market.exit(&crate::ID)?;
+        }
+        let potential_payout = parlay::potential_payout(stake, legs.iter().map(|leg| leg.odds))?;
+
+        let cpi_accounts = anchor_lang::system_program::Transfer{
+            from: ctx.accounts.bettor.to_account_info(),
+            to: ctx.accounts.house.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        anchor_lang::system_program::transfer(cpi_ctx, stake)?;
+
+        let house = &mut ctx.accounts.house;
+        let reserve = parlay::reserve(stake, potential_payout);
+        require!(reserve <= house_free_balance(house)?, ErrorCode::HouseUnderfunded);
+        house.reserved += reserve;
+        house.parlay_count += 1;
+
+        let parlay = &mut ctx.accounts.parlay;
+        parlay.bettor = ctx.accounts.bettor.key();
+        parlay.id = house.parlay_count - 1;
+        parlay.stake = stake;
+        parlay.legs = legs;
+        parlay.potential_payout = potential_payout;
+        parlay.bump = ctx.bumps.parlay;
+
+        emit!(ParlayPlaced {
+            parlay: parlay.key(),
+            bettor: parlay.bettor,
+            stake,
+            legs: parlay.legs.clone(),
+            potential_payout,
+        });
+        Ok(())
+    }
+
+    // permissionless and lazy: settles once every leg's market is resolved or refundable, or as
+    // soon as one leg lost. Legs whose market was refunded drop out at even odds. The legs'
+    // markets go (writable) in remaining_accounts in leg order; they can't be finalized while
+    // the parlay is open.
+    pub fn settle_parlay<'info>(ctx: Context<'_, '_, 'info, 'info, SettleParlay<'info>>) -> Result<()> {
+        let parlay = &ctx.accounts.parlay;
+        require!(ctx.remaining_accounts.len() == parlay.legs.len(), ErrorCode::InvalidParlayLegs);
+
+        let mut results = Vec::with_capacity(parlay.legs.len());
+        for (market_info, leg) in ctx.remaining_accounts.iter().zip(&parlay.legs) {
+            require!(market_info.key() == leg.market, ErrorCode::InvalidParlayLegs);
+            let market = Account::<Market>::try_from(market_info)?;
+            results.push(if market.refundable {
+                LegResult::Cancelled
+            } else if !market.resolved {
+                LegResult::Pending
+            } else if market.winning_outcome == Some(leg.outcome) {
+                LegResult::Won
+            } else {
+                LegResult::Lost
+            });
+        }
+        let payout = parlay::settle(parlay.stake, &parlay.legs, &results)?.ok_or(ErrorCode::ParlayNotSettled)?;
+        for market_info in ctx.remaining_accounts {
+            let mut market = Account::<Market>::try_from(market_info)?;
+            market.open_parlay_legs -= 1;
+            market.exit(&crate::ID)?;
+        }
+
+        let house = &mut ctx.accounts.house;
+        house.reserved -= parlay::reserve(parlay.stake, parlay.potential_payout);
+        transfer_lamports(&house.to_account_info(), &ctx.accounts.bettor.to_account_info(), payout)?;
+
+        emit!(ParlaySettled { parlay: parlay.key(), bettor: parlay.bettor, payout });
+        Ok(()) // the parlay account is closed back to the bettor
+    }
+
+    // read-only view for dashboards, returned through return data (simulate the transaction to read it)
+    pub fn get_market_stats(ctx: Context<GetMarketStats>) -> Result<MarketStats> {
+        let market = &ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        let implied_probabilities_bps = match &market.lmsr {
+            // LMSR markets quote their own prices instead of pooling bets
+            Some(lmsr) => (0..market.outcomes.len())
+                .map(|outcome| Ok((lmsr.price(outcome)? * 10_000 / lmsr::SCALE) as u16))
+                .collect::<Result<Vec<u16>>>()?,
+            None => events::implied_probabilities_bps(&market.total_bets),
+        };
+        Ok(MarketStats {
+            implied_probabilities_bps,
+            pool_size: market.total_bets.iter().sum::<u64>() + market.exit_fees,
+            bettor_count: market.bettor_count,
+            time_left: (market.betting_close_time - clock.unix_timestamp).max(0),
+        })
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeFactory<'info> {
+    #[account(init, payer = admin, space = 8 + MarketFactory::INIT_SPACE, seeds = [b"factory"], bump)]
+    pub factory: Account<'info, MarketFactory>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFactory<'info> {
+    #[account(mut, seeds = [b"factory"], bump = factory.bump, has_one = admin)]
+    pub factory: Account<'info, MarketFactory>,
+    pub admin: Signer<'info>,
+    pub treasury: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCreatorAllowed<'info> {
+    #[account(seeds = [b"factory"], bump = factory.bump, has_one = admin)]
+    pub factory: Account<'info, MarketFactory>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: only used as the allowlist key
+    pub creator: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CreatorAllowance::INIT_SPACE,
+        seeds = [b"creator", creator.key().as_ref()],
+        bump
+    )]
+    pub creator_allowance: Account<'info, CreatorAllowance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(category: MarketCategory)]
+pub struct InitCategoryIndex<'info> {
+    #[account(seeds = [b"factory"], bump = factory.bump, has_one = admin)]
+    pub factory: Account<'info, MarketFactory>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = CategoryIndex::space(0),
+        seeds = [b"category_index".as_ref(), &[category as u8]],
+        bump
+    )]
+    pub category_index: Account<'info, CategoryIndex>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)] // creating a market account like eg ipl teams csk, rcb etc
+pub struct CreateMarket<'info> {
+    #[account(
+        init,
+        payer=authority,
+        space=Market::SPACE,
+        seeds = [b"market", factory.market_count.to_le_bytes().as_ref()], // the factory counter keeps every market enumerable
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(mut, seeds = [b"factory"], bump = factory.bump)]
+    pub factory: Box<Account<'info, MarketFactory>>,
+    #[account(mut, address = factory.treasury)]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"category_index".as_ref(), &[category_index.category as u8]],
+        bump = category_index.bump,
+        realloc = CategoryIndex::space(category_index.market_ids.len() + 1),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+    pub category_index: Box<Account<'info, CategoryIndex>>,
+    // required while the factory allowlist is on
+    #[account(seeds = [b"creator", authority.key().as_ref()], bump = creator_allowance.bump)]
+    pub creator_allowance: Option<Account<'info, CreatorAllowance>>,
+    pub collateral_mint: Option<Account<'info, Mint>>, // None for SOL markets
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vault", market.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = market
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub parent_market: Option<Account<'info, Market>>, // makes this a conditional market
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateSeries<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = MarketSeries::SPACE,
+        seeds = [b"series", authority.key().as_ref(), name.as_bytes()],
+        bump
+    )]
+    pub series: Box<Account<'info, MarketSeries>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"factory"], bump = factory.bump)]
+    pub factory: Account<'info, MarketFactory>,
+    // required while the factory allowlist is on
+    #[account(seeds = [b"creator", authority.key().as_ref()], bump = creator_allowance.bump)]
+    pub creator_allowance: Option<Account<'info, CreatorAllowance>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpawnNextMarket<'info> {
+    #[account(mut, seeds = [b"series", series.authority.as_ref(), series.name.as_bytes()], bump = series.bump)]
+    pub series: Box<Account<'info, MarketSeries>>,
+    #[account(
+        init,
+        payer = payer,
+        space = Market::SPACE,
+        seeds = [b"market", factory.market_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub market: Box<Account<'info, Market>>,
+    /// CHECK: only compared against series.last_market, none for the first round
+    pub previous_market: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"factory"], bump = factory.bump)]
+    pub factory: Box<Account<'info, MarketFactory>>,
+    #[account(mut, address = factory.treasury)]
+    pub treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"category_index".as_ref(), &[category_index.category as u8]],
+        bump = category_index.bump,
+        realloc = CategoryIndex::space(category_index.market_ids.len() + 1),
+        realloc::payer = payer,
+        realloc::zero = false
+    )]
+    pub category_index: Box<Account<'info, CategoryIndex>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarketMetadata<'info> {
+    #[account(mut, has_one = authority)]
+    pub market: Account<'info, Market>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)] // an accouint which will have the details about which team / market did the user bet on
+#[instruction(outcome_index:u8, amount: u64)] // the arguments the user will provide
+pub struct PlaceBet<'info>{
+    #[account(mut, has_one = authority)] // //authority not in scope so this is checking the market account whether it is from the correct authority or not
+    pub market: Account<'info, Market>, // the market account to bet on
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = bettor,
+        space = 8+32+1+8+1,
+        seeds = [b"bet", market.key().as_ref(), bettor.key().as_ref()],
+        bump
+    )] // this macro is used to create a required bet account
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub bettor: Signer<'info>, 
+    pub system_program: Program<'info, System>,
+    // only for SPL collateral markets
+    #[account(mut, seeds = [b"vault", market.key().as_ref()], bump)]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = bettor)]
+    pub bettor_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+
+#[derive(Accounts)]
+pub struct VoteResolution<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    pub member: Signer<'info>,
+    pub parent_market: Option<Account<'info, Market>>, // only for conditional markets
+}
+
+#[derive(Accounts)]
+pub struct FinalizeMarket<'info> {
+    #[account(mut, has_one = authority, close = authority)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub authority: SystemAccount<'info>,
+    #[account(seeds = [b"factory"], bump = factory.bump, has_one = treasury)]
+    pub factory: Account<'info, MarketFactory>,
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+    // only for SPL collateral markets
+    #[account(mut, seeds = [b"vault", market.key().as_ref()], bump)]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = treasury)]
+    pub treasury_token: Option<Account<'info, TokenAccount>>,
+    // only for refundable SPL collateral markets with exit fees to return
+    #[account(mut, token::authority = authority)]
+    pub authority_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct InitParlayHouse<'info> {
+    #[account(init, payer = admin, space = 8 + ParlayHouse::INIT_SPACE, seeds = [b"house"], bump)]
+    pub house: Account<'info, ParlayHouse>,
+    #[account(seeds = [b"factory"], bump = factory.bump, has_one = admin)]
+    pub factory: Account<'info, MarketFactory>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundParlayHouse<'info> {
+    #[account(mut, seeds = [b"house"], bump = house.bump)]
+    pub house: Account<'info, ParlayHouse>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawParlayHouse<'info> {
+    #[account(mut, seeds = [b"house"], bump = house.bump, has_one = admin)]
+    pub house: Account<'info, ParlayHouse>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceParlay<'info> {
+    #[account(
+        init,
+        payer = bettor,
+        space = Parlay::SPACE,
+        seeds = [b"parlay", bettor.key().as_ref(), house.parlay_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub parlay: Account<'info, Parlay>,
+    #[account(mut, seeds = [b"house"], bump = house.bump)]
+    pub house: Account<'info, ParlayHouse>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleParlay<'info> {
+    #[account(mut, has_one = bettor, close = bettor)]
+    pub parlay: Account<'info, Parlay>,
+    #[account(mut, seeds = [b"house"], bump = house.bump)]
+    pub house: Account<'info, ParlayHouse>,
+    #[account(mut)]
+    pub bettor: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetMarketStats<'info> {
+    pub market: Account<'info, Market>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMarket<'info> {
+    #[account(mut, has_one=authority)]
+    pub market: Account<'info, Market>,
+    pub authority: Signer<'info>,
+    pub parent_market: Option<Account<'info, Market>>, // only for conditional markets
+}
+
+#[derive(Accounts)]
+pub struct ResolveWithOracle<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the feed pinned in market.oracle, its data is parsed by oracle::PriceFeed
+    pub price_feed: UncheckedAccount<'info>,
+    pub parent_market: Option<Account<'info, Market>>, // only for conditional markets
+}
+
+#[derive(Accounts)]
+pub struct ProposeOutcome<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub parent_market: Option<Account<'info, Market>>, // only for conditional markets
+}
+
+#[derive(Accounts)]
+pub struct DisputeOutcome<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub disputer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeOutcome<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    /// CHECK: checked against market.proposer, only receives the bond back
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Arbitrate<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    pub arbiter: Signer<'info>,
+    /// CHECK: checked against market.proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+    /// CHECK: checked against market.disputer
+    #[account(mut)]
+    pub disputer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireDispute<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    /// CHECK: checked against market.proposer, only receives the bond back
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+    /// CHECK: checked against market.disputer, only receives the bond back
+    #[account(mut)]
+    pub disputer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuyShares<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        init_if_needed,
+        payer = trader,
+        space = 8 + 32 + 32 + 4 + 2 * 8,
+        seeds = [b"position", market.key().as_ref(), trader.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut)]
+    pub trader: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SellShares<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), trader.key().as_ref()],
+        bump,
+        constraint = position.owner == trader.key() @ ErrorCode::Unauthorized
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut)]
+    pub trader: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemShares<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), trader.key().as_ref()],
+        bump,
+        constraint = position.owner == trader.key() @ ErrorCode::Unauthorized
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut)]
+    pub trader: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(outcome: u8)]
+pub struct InitOrderBook<'info> {
+    pub market: Account<'info, Market>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<OrderBook>(),
+        seeds = [b"book", market.key().as_ref(), &[outcome]],
+        bump
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    pub outcome_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"book_escrow", order_book.key().as_ref()],
+        bump,
+        token::mint = outcome_mint,
+        token::authority = market
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceOrder<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, constraint = order_book.load()?.market == market.key() @ ErrorCode::OrderBookMismatch)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(address = order_book.load()?.mint @ ErrorCode::OrderBookMismatch)]
+    pub outcome_mint: Account<'info, Mint>,
+    #[account(mut, seeds = [b"book_escrow", order_book.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    // where asks come from and fills are delivered to
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = outcome_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, constraint = order_book.load()?.market == market.key() @ ErrorCode::OrderBookMismatch)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(mut, seeds = [b"book_escrow", order_book.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = escrow.mint, token::authority = owner)]
+    pub owner_token: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MatchOrders<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, constraint = order_book.load()?.market == market.key() @ ErrorCode::OrderBookMismatch)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(mut, seeds = [b"book_escrow", order_book.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitOutcomeMints<'info> {
+    #[account(mut, has_one = authority)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"outcome_mint", market.key().as_ref(), &[0]],
+        bump,
+        mint::decimals = 9, // 1 token base unit is backed by 1 lamport
+        mint::authority = market
+    )]
+    pub outcome_mint_0: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"outcome_mint", market.key().as_ref(), &[1]],
+        bump,
+        mint::decimals = 9,
+        mint::authority = market
+    )]
+    pub outcome_mint_1: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SplitMerge<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"outcome_mint", market.key().as_ref(), &[0]], bump)]
+    pub outcome_mint_0: Account<'info, Mint>,
+    #[account(mut, seeds = [b"outcome_mint", market.key().as_ref(), &[1]], bump)]
+    pub outcome_mint_1: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = outcome_mint_0,
+        associated_token::authority = user
+    )]
+    pub user_token_0: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = outcome_mint_1,
+        associated_token::authority = user
+    )]
+    pub user_token_1: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub winning_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = winning_mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RefundTokens<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub outcome_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = outcome_mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MarkRefundable<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    pub parent_market: Option<Account<'info, Market>>, // only for conditional markets
+}
+
+#[derive(Accounts)]
+pub struct ClaimRefund<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        close = bettor,
+        seeds = [b"bet", market.key().as_ref(), bettor.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    // only for SPL collateral markets
+    #[account(mut, seeds = [b"vault", market.key().as_ref()], bump)]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = bettor)]
+    pub bettor_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawBet<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        close = bettor,
+        seeds = [b"bet", market.key().as_ref(), bettor.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    // only for SPL collateral markets
+    #[account(mut, seeds = [b"vault", market.key().as_ref()], bump)]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = bettor)]
+    pub bettor_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + LpPosition::INIT_SPACE,
+        seeds = [b"lp", market.key().as_ref(), provider.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        seeds = [b"lp", market.key().as_ref(), provider.key().as_ref()],
+        bump,
+        constraint = lp_position.owner == provider.key() @ ErrorCode::Unauthorized
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimMany<'info> {
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPayout<'info>{
+    #[account(mut, has_one = authority)] //authority not in scope
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut, seeds = [b"bet", market.key().as_ref(), bettor.key().as_ref()], bump)]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)] // Mutable bettor account to receive payout.
+    pub bettor: Signer<'info>, // Bettor must sign.
+    // only for SPL collateral markets
+    #[account(mut, seeds = [b"vault", market.key().as_ref()], bump)]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = bettor)]
+    pub bettor_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[account]
+pub struct Bet{
+    pub bettor: Pubkey,
+    pub outcome_index: u8,
+    pub amount: u64,
+    pub claimed: bool, // set by claim_payout / claim_many so a bet only pays out once
+}
+
+#[account]
+pub struct Position{
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub shares: Vec<u64>, // LMSR shares held per outcome
+}
+
+#[account]
+pub struct MarketSeries {
+    pub authority: Pubkey,
+    pub name: String,
+    pub question_format: String,
+    pub outcomes: Vec<String>,
+    pub duration: i64,
+    pub resolution_window: i64,
+    pub oracle: Option<OracleConfig>,
+    pub metadata: MarketMetadata,
+    pub limits: BetLimits,
+    pub next_round: u64,
+    pub next_round_at: i64,
+    pub last_market: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl MarketSeries {
+    pub const SPACE: usize = 8 + 32 + 4 + series::MAX_SERIES_NAME_LEN + 4 + 180 + 4 + 2 * (4 + 50) + 8 + 8
+        + 1 + OracleConfig::SIZE
+        + MarketMetadata::SIZE
+        + BetLimits::SIZE
+        + 8 + 8 + 33 + 1;
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LpPosition {
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub shares: u64, // claim on market.lp_stake out of market.lp_shares
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ParlayHouse {
+    pub admin: Pubkey,
+    pub reserved: u64, // potential payouts of every open parlay
+    pub parlay_count: u64, // next parlay id, used in the parlay PDA seeds
+    pub bump: u8,
+}
+
+#[account]
+pub struct Parlay {
+    pub bettor: Pubkey,
+    pub id: u64,
+    pub stake: u64,
+    pub legs: Vec<ParlayLeg>,
+    pub potential_payout: u64, // the stake at every leg's odds, less the edge; the house reserves at least the stake
+    pub bump: u8,
+}
+
+impl Parlay {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + 4 + parlay::MAX_LEGS * ParlayLeg::SIZE + 8 + 1;
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MarketFactory{
+    pub admin: Pubkey,
+    pub treasury: Pubkey, // receives creation fees
+    pub creation_fee: u64,
+    pub allowlist_enabled: bool, // only creators with an allowed CreatorAllowance can create markets
+    pub market_count: u64, // next market id, used as the market PDA seed
+    pub bump: u8,
+    pub claim_window: i64, // seconds after a market's resolution deadline before it can be finalized
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CreatorAllowance{
+    pub creator: Pubkey,
+    pub allowed: bool,
+    pub bump: u8,
+}
+
+#[account]
+pub struct CategoryIndex{
+    pub category: MarketCategory,
+    pub market_ids: Vec<u64>, // grows by one on every create_market in this category
+    pub bump: u8,
+}
+
+impl CategoryIndex {
+    pub fn space(markets: usize) -> usize {
+        8 + 1 + 4 + markets * 8 + 1
+    }
+}
+
+impl Position {
+    // fills in a freshly created (init_if_needed) position
+    pub fn open(&mut self, owner: Pubkey, market: &Account<Market>) -> Result<()> {
+        if self.shares.is_empty() {
+            self.owner = owner;
+            self.market = market.key();
+            self.shares = vec![0; market.outcomes.len()];
+        }
+        require!(self.owner == owner, ErrorCode::Unauthorized);
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Market{
+    pub id: u64,
+    pub authority: Pubkey,
+    pub question: String,
+    pub outcomes: Vec<String>,
+    pub betting_close_time: i64,
+    pub event_time: i64,
+    pub resolution_deadline: i64,
+    pub resolved: bool,
+    pub winning_outcome: Option<u8> ,// this one is doubt option is given because at the start is None after the resolution time the winner index is decided so we wait until then
+    pub total_bets: Vec<u64>,
+    pub is_active: bool,
+    pub refundable: bool, // set once resolution_deadline passes without a result, bets can be refunded
+    pub metadata: MarketMetadata,
+    pub oracle: Option<OracleConfig>, // Some for price markets resolved by resolve_with_oracle
+    pub optimistic: Option<OptimisticConfig>, // Some for markets resolved by proposal + dispute
+    pub committee: Option<Committee>, // Some for markets resolved by committee vote
+    pub proposer: Option<Pubkey>,
+    pub proposed_outcome: Option<u8>,
+    pub proposed_at: i64,
+    pub disputer: Option<Pubkey>,
+    pub lmsr: Option<Lmsr>, // Some for markets priced by the LMSR market maker
+    pub bump: u8,
+    pub outcome_mints: Vec<Pubkey>, // empty until init_outcome_mints
+    pub split_collateral: u64, // lamports backing outstanding outcome token sets
+    pub collateral_mint: Option<Pubkey>, // None for SOL markets, bets then sit in the vault PDA
+    pub parent_market: Option<Pubkey>, // Some for conditional markets
+    pub parent_outcome: u8, // outcome the parent has to resolve to for this market to resolve
+    pub kind: MarketKind,
+    pub resolved_value: Option<i64>, // Some once a scalar market is resolved
+    pub bettor_count: u32,
+    pub limits: BetLimits,
+    pub created_at: i64,
+    pub exit_fees: u64, // withdrawal fees left in the pool, paid out to winners with it
+    pub lp_stake: u64, // liquidity seeded on each outcome, already counted in total_bets
+    pub lp_shares: u64,
+    pub paid_out: u64, // claims paid so far; the pool minus this is unclaimed winnings and dust
+    pub claim_deadline: i64, // finalize_market sweeps whatever is left after this
+    pub series: Option<Pubkey>, // Some for markets spawned from a MarketSeries
+    pub previous_market: Option<Pubkey>, // the series' previous round
+    pub open_parlay_legs: u32, // unsettled parlays with a leg on this market
+    pub claim_window: i64, // the factory's claim window when the market was created
+}
+
+impl Market {
+    pub const SPACE: usize = 8 + 8 + 32 + 4 + 200 + 4 + 2 * (4 + 50) + 8 + 1 + 4 + 2 * 8 + 1 + 8
+        + 8 + 8 + 1
+        + MarketMetadata::SIZE
+        + 1 + OracleConfig::SIZE
+        + 1 + OptimisticConfig::SIZE + 33 + 2 + 8 + 33
+        + 1 + Lmsr::SIZE
+        + 1 + 4 + 2 * 32 + 8
+        + 33
+        + 33 + 1
+        + MarketKind::SIZE + 9
+        + 4
+        + BetLimits::SIZE
+        + 8 + 8
+        + 1 + Committee::SIZE
+        + 8 + 8
+        + 8
+        + 8
+        + 33 + 33
+        + 4
+        + 8;
+}
+
+// what a bet is owed from a resolved market, zero for losing bets
+fn bet_payout(market: &Market, bet: &Bet) -> Result<u64> {
+    Ok(payout::payout(market.settlement()?, &market.total_bets, market.exit_fees, bet.outcome_index, bet.amount))
+}
+
+fn emit_resolved(market: &Account<Market>) -> Result<()> {
+    emit!(MarketResolved {
+        market: market.key(),
+        winning_outcome: market.winning_outcome,
+        resolved_value: market.resolved_value,
+        resolved_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+impl Market {
+    // winners and refunds always get a full claim window, however late the market settles
+    pub fn open_claim_window(&mut self, now: i64) {
+        self.claim_deadline = self.claim_deadline.max(now.saturating_add(self.claim_window));
+    }
+
+    pub fn settlement(&self) -> Result<Settlement> {
+        require!(self.resolved, ErrorCode::MarketNotResolved);
+        match self.kind {
+            MarketKind::Binary => Ok(Settlement::Winner(self.winning_outcome.ok_or(ErrorCode::MarketNotResolved)?)),
+            MarketKind::Scalar { min, max } => {
+                let value = self.resolved_value.ok_or(ErrorCode::MarketNotResolved)?;
+                Ok(Settlement::Scalar { min, max, value })
+            }
+        }
+    }
+}
+
+// conditional markets only resolve once the parent resolved to the required outcome
+fn check_parent_condition(market: &Market, parent: &Option<Account<Market>>) -> Result<()> {
+    let Some(parent_key) = market.parent_market else {
+        return Ok(());
+    };
+    let parent = parent.as_ref().ok_or(ErrorCode::ParentMarketMismatch)?;
+    require!(parent.key() == parent_key, ErrorCode::ParentMarketMismatch);
+    require!(parent.resolved, ErrorCode::ParentNotResolved);
+    require!(parent.winning_outcome == Some(market.parent_outcome), ErrorCode::ParentConditionFailed);
+    Ok(())
+}
+
+// pays `amount` of the market's collateral to `to`: out of the vault for SPL markets, from the market's lamports for SOL
+fn pay_from_market<'info>(
+    market: &Account<'info, Market>,
+    to: &AccountInfo<'info>,
+    vault: &Option<Account<'info, TokenAccount>>,
+    to_token: &Option<Account<'info, TokenAccount>>,
+    token_program: &Option<Program<'info, Token>>,
+    amount: u64,
+) -> Result<()> {
+    if market.collateral_mint.is_none() {
+        return transfer_lamports(&market.to_account_info(), to, amount);
+    }
+    let (Some(vault), Some(to_token), Some(token_program)) = (vault, to_token, token_program) else {
+        return err!(ErrorCode::MissingTokenAccounts);
+    };
+
+    // the vault is owned by the market PDA, so the market signs for the transfer out
+    let id = market.id.to_le_bytes();
+    let bump = [market.bump];
+    let signer_seeds: &[&[u8]] = &[b"market", &id, &bump];
+    let signers = &[signer_seeds];
+    let cpi_accounts = TokenTransfer {
+        from: vault.to_account_info(),
+        to: to_token.to_account_info(),
+        authority: market.to_account_info(),
+    };
+    token::transfer(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signers), amount)
+}
+
+fn find_wallet<'a, 'info>(accounts: &'a [AccountInfo<'info>], owner: Pubkey) -> Result<&'a AccountInfo<'info>> {
+    accounts
+        .iter()
+        .find(|account| account.key() == owner && account.is_writable)
+        .ok_or_else(|| error!(ErrorCode::MissingSettlementAccount))
+}
+
+// the owner's associated token account for `mint`, as created by place_order
+fn find_token_account<'a, 'info>(accounts: &'a [AccountInfo<'info>], owner: Pubkey, mint: Pubkey) -> Result<&'a AccountInfo<'info>> {
+    let address = anchor_spl::associated_token::get_associated_token_address(&owner, &mint);
+    accounts
+        .iter()
+        .find(|account| account.key() == address && account.is_writable)
+        .ok_or_else(|| error!(ErrorCode::MissingSettlementAccount))
+}
+
+// house lamports not needed for rent or reserved for open parlays
+fn house_free_balance(house: &Account<ParlayHouse>) -> Result<u64> {
+    let info = house.to_account_info();
+    let rent = Rent::get()?.minimum_balance(info.data_len());
+    Ok(info.lamports().saturating_sub(rent).saturating_sub(house.reserved))
+}
+
+// the market account is owned by this program, so it can move its own lamports directly
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    **from.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? += amount;
+    Ok(())
 }
 
 #[error_code]
@@ -182,4 +2331,85 @@ pub enum ErrorCode{
     #[msg("Unauthorized access")] Unauthorized,
     #[msg("Betting closed")] BettingClosed,
     #[msg("Invalid bet amount")] InvalidBetAmount,
+    #[msg("Invalid oracle configuration")] InvalidOracleConfig,
+    #[msg("Market is not an oracle market")] NotOracleMarket,
+    #[msg("Oracle markets must be resolved with resolve_with_oracle")] OracleMarket,
+    #[msg("Price feed does not match the market")] OracleFeedMismatch,
+    #[msg("Invalid price feed account")] InvalidPriceFeed,
+    #[msg("Oracle price is stale")] StalePrice,
+    #[msg("Oracle price was published before the event time")] PricePublishedTooEarly,
+    #[msg("Oracle confidence interval is too wide")] PriceConfidenceTooWide,
+    #[msg("Invalid optimistic resolution configuration")] InvalidOptimisticConfig,
+    #[msg("Market is not an optimistic market")] NotOptimisticMarket,
+    #[msg("Optimistic markets must be resolved through proposals")] OptimisticMarket,
+    #[msg("An outcome has already been proposed")] ProposalExists,
+    #[msg("No outcome has been proposed")] NoProposal,
+    #[msg("Proposal has already been disputed")] AlreadyDisputed,
+    #[msg("Proposal has not been disputed")] NotDisputed,
+    #[msg("Challenge window has closed")] ChallengeWindowClosed,
+    #[msg("Challenge window is still open")] ChallengeWindowOpen,
+    #[msg("Proposer account does not match")] ProposerMismatch,
+    #[msg("Disputer account does not match")] DisputerMismatch,
+    #[msg("Invalid liquidity parameter")] InvalidLiquidity,
+    #[msg("Market is not an LMSR market")] NotLmsrMarket,
+    #[msg("LMSR markets trade through buy_shares and sell_shares")] LmsrMarket,
+    #[msg("Price moved beyond the allowed slippage")] SlippageExceeded,
+    #[msg("Not enough shares")] InsufficientShares,
+    #[msg("Math overflow")] MathOverflow,
+    #[msg("Outcome mints already initialized")] OutcomeMintsExist,
+    #[msg("Token accounts are required for SPL collateral markets")] MissingTokenAccounts,
+    #[msg("Only available for SOL collateral markets")] SolCollateralOnly,
+    #[msg("Resolution deadline has passed")] ResolutionDeadlinePassed,
+    #[msg("Resolution deadline has not passed yet")] ResolutionDeadlineNotPassed,
+    #[msg("Market is not refundable")] MarketNotRefundable,
+    #[msg("Outcome label is too long")] OutcomeTooLong,
+    #[msg("Too many tags")] TooManyTags,
+    #[msg("Tag is too long")] TagTooLong,
+    #[msg("Resolution source is too long")] ResolutionSourceTooLong,
+    #[msg("Metadata URI is too long")] UriTooLong,
+    #[msg("Market already has bets")] MarketHasBets,
+    #[msg("Creator is not on the allowlist")] CreatorNotAllowed,
+    #[msg("Category index does not match the market category")] CategoryMismatch,
+    #[msg("Invalid parent market")] InvalidParentMarket,
+    #[msg("Parent market account does not match")] ParentMarketMismatch,
+    #[msg("Parent market is not resolved")] ParentNotResolved,
+    #[msg("Parent market resolved to a different outcome")] ParentConditionFailed,
+    #[msg("Invalid scalar market range")] InvalidScalarRange,
+    #[msg("Market is not a scalar market")] NotScalarMarket,
+    #[msg("Scalar markets must be resolved with resolve_scalar")] ScalarMarket,
+    #[msg("Order price must be between 1 and 9999 bps")] InvalidOrderPrice,
+    #[msg("Order quantity must be a positive multiple of the lot size")] InvalidOrderQuantity,
+    #[msg("Order book is full")] OrderBookFull,
+    #[msg("Order not found")] OrderNotFound,
+    #[msg("Order book does not belong to this market")] OrderBookMismatch,
+    #[msg("A settlement account is missing from remaining accounts")] MissingSettlementAccount,
+    #[msg("Invalid bet limits")] InvalidBetLimits,
+    #[msg("Bet is below the market minimum")] BetBelowMinimum,
+    #[msg("Bet is above the per-bettor maximum")] BetAboveMaximum,
+    #[msg("Bet would push the outcome over its maximum share of the pool")] OutcomeShareExceeded,
+    #[msg("Bettor is not on the market allowlist")] NotAllowlisted,
+    #[msg("Invalid resolver committee")] InvalidCommittee,
+    #[msg("Market is not resolved by a committee")] NotCommitteeMarket,
+    #[msg("Committee markets must be resolved with vote_resolution")] CommitteeMarket,
+    #[msg("Signer is not on the resolver committee")] NotCommitteeMember,
+    #[msg("Committee member has already voted")] AlreadyVoted,
+    #[msg("Liquidity is locked between betting close and settlement")] LiquidityLocked,
+    #[msg("Bet has already been claimed")] AlreadyClaimed,
+    #[msg("Remaining accounts must be (market, bet) pairs of this bettor")] InvalidClaimAccounts,
+    #[msg("Claim window is shorter than the minimum")] InvalidClaimWindow,
+    #[msg("Market is neither resolved nor refundable")] MarketNotSettled,
+    #[msg("Claim period has not ended yet")] ClaimPeriodOpen,
+    #[msg("Invalid market series")] InvalidSeries,
+    #[msg("The next round of this series has not opened yet")] RoundNotOpen,
+    #[msg("Previous market does not match the series")] PreviousMarketMismatch,
+    #[msg("A parlay needs 2 to 5 legs on distinct markets, passed in leg order")] InvalidParlayLegs,
+    #[msg("Nobody has bet on this outcome yet, so it has no odds")] NoOddsAvailable,
+    #[msg("Parlay house cannot cover the payout")] HouseUnderfunded,
+    #[msg("Some parlay legs are still unresolved")] ParlayNotSettled,
+    #[msg("Arbitration window has closed")] ArbitrationClosed,
+    #[msg("Arbitration window is still open")] ArbitrationWindowOpen,
+    #[msg("Outcome mints have not been initialized")] OutcomeMintsMissing,
+    #[msg("Open parlays have a leg on this market")] ParlayLegsOpen,
+    #[msg("Parlay stake is below the minimum")] ParlayStakeTooSmall,
+    #[msg("Order is below the minimum size")] OrderTooSmall,
 }
@@ -0,0 +1,106 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+use crate::ErrorCode;
+
+pub const MAX_PROOF_LEN: usize = 20; // enough for ~1M allowlisted wallets
+
+// per-market guards enforced by place_bet; zero means "no limit" for the caps
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BetLimits {
+    pub min_bet: u64,
+    pub max_bet_per_bettor: u64,
+    pub max_outcome_share_bps: u16, // largest share of the pool one outcome may hold once others have bets
+    pub allowlist_root: Option<[u8; 32]>, // Merkle root of sha256(wallet) leaves for private markets
+}
+
+impl BetLimits {
+    pub const SIZE: usize = 8 + 8 + 2 + 33;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.max_outcome_share_bps <= 10_000, ErrorCode::InvalidBetLimits);
+        if self.max_bet_per_bettor > 0 {
+            require!(self.max_bet_per_bettor >= self.min_bet, ErrorCode::InvalidBetLimits);
+        }
+        Ok(())
+    }
+
+    // `total_bets` are the pools before this bet; each bettor has a single bet account per market
+    pub fn check(&self, total_bets: &[u64], outcome: usize, amount: u64) -> Result<()> {
+        require!(amount >= self.min_bet, ErrorCode::BetBelowMinimum);
+        if self.max_bet_per_bettor > 0 {
+            require!(amount <= self.max_bet_per_bettor, ErrorCode::BetAboveMaximum);
+        }
+
+        // a lone outcome is always 100% of the pool, so the cap only bites once the others have bets
+        let others: u64 = total_bets.iter().enumerate().filter(|(i, _)| *i != outcome).map(|(_, total)| total).sum();
+        if self.max_outcome_share_bps > 0 && others > 0 {
+            let outcome_total = total_bets[outcome] as u128 + amount as u128;
+            let pool = outcome_total + others as u128;
+            require!(outcome_total * 10_000 <= pool * self.max_outcome_share_bps as u128, ErrorCode::OutcomeShareExceeded);
+        }
+        Ok(())
+    }
+
+    pub fn check_allowlist(&self, wallet: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        let Some(root) = self.allowlist_root else {
+            return Ok(());
+        };
+        require!(proof.len() <= MAX_PROOF_LEN, ErrorCode::NotAllowlisted);
+        require!(merkle_root(wallet, proof) == root, ErrorCode::NotAllowlisted);
+        Ok(())
+    }
+}
+
+// pairs are hashed in sorted order, so proofs don't need to say which side each sibling is on
+pub fn merkle_root(wallet: &Pubkey, proof: &[[u8; 32]]) -> [u8; 32] {
+    proof.iter().fold(hashv(&[wallet.as_ref()]).to_bytes(), |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b { hashv(&[&a, &b]).to_bytes() } else { hashv(&[&b, &a]).to_bytes() }
+    }
+
+    #[test]
+    fn enforces_min_and_max_bets() {
+        let limits = BetLimits { min_bet: 100, max_bet_per_bettor: 1_000, ..Default::default() };
+        assert!(limits.check(&[0, 0], 0, 99).is_err());
+        assert!(limits.check(&[0, 0], 0, 1_000).is_ok());
+        assert!(limits.check(&[0, 0], 0, 1_001).is_err());
+    }
+
+    #[test]
+    fn caps_an_outcome_once_the_other_side_has_bets() {
+        let limits = BetLimits { max_outcome_share_bps: 8_000, ..Default::default() };
+        assert!(limits.check(&[0, 0], 0, 1_000_000).is_ok());
+        assert!(limits.check(&[100, 0], 1, 400).is_ok());
+        assert!(limits.check(&[100, 0], 1, 401).is_err());
+        assert!(limits.check(&[100, 100], 0, 300).is_ok());
+        assert!(limits.check(&[100, 100], 0, 301).is_err());
+    }
+
+    #[test]
+    fn verifies_allowlist_proofs() {
+        let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = wallets.iter().map(|w| hashv(&[w.as_ref()]).to_bytes()).collect();
+        let left = hash_pair(leaves[0], leaves[1]);
+        let root = hash_pair(left, leaves[2]);
+        let limits = BetLimits { allowlist_root: Some(root), ..Default::default() };
+
+        assert!(limits.check_allowlist(&wallets[0], &[leaves[1], leaves[2]]).is_ok());
+        assert!(limits.check_allowlist(&wallets[2], &[left]).is_ok());
+        assert!(limits.check_allowlist(&wallets[1], &[leaves[2]]).is_err());
+        assert!(limits.check_allowlist(&Pubkey::new_unique(), &[left]).is_err());
+        assert!(BetLimits::default().check_allowlist(&Pubkey::new_unique(), &[]).is_ok());
+    }
+}
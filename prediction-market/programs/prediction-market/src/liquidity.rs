@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// LPs seed every outcome with the same stake, so the pool as a whole holds `lp_stake` on each
+// outcome and behaves like one more bettor backing all of them. LP shares are claims on that
+// stake: pro rata before resolution, and on whatever the stake won (winnings, exit fees and
+// rounding dust included) after it. In a refundable market nobody won, so LPs take the exit fees.
+
+// shares minted for adding `per_outcome` to every outcome's stake
+pub fn shares_for_deposit(per_outcome: u64, lp_stake: u64, lp_shares: u64) -> Result<u64> {
+    if lp_shares == 0 || lp_stake == 0 {
+        return Ok(per_outcome);
+    }
+    let shares = per_outcome as u128 * lp_shares as u128 / lp_stake as u128;
+    require!(shares > 0, ErrorCode::InvalidBetAmount);
+    u64::try_from(shares).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+// the per-outcome stake `shares` out of `lp_shares` are entitled to
+pub fn stake_for_shares(shares: u64, lp_stake: u64, lp_shares: u64) -> u64 {
+    (shares as u128 * lp_stake as u128 / lp_shares as u128) as u64
+}
+
+// the part of a refundable market's exit fees `shares` out of `lp_shares` are entitled to
+pub fn fee_share(shares: u64, exit_fees: u64, lp_shares: u64) -> u64 {
+    (shares as u128 * exit_fees as u128 / lp_shares as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_deposit_mints_one_share_per_lamport() {
+        assert_eq!(shares_for_deposit(500, 0, 0).unwrap(), 500);
+    }
+
+    #[test]
+    fn later_deposits_mint_pro_rata() {
+        // 1_000 shares over 2_000 stake: every share is worth 2 lamports per outcome
+        assert_eq!(shares_for_deposit(500, 2_000, 1_000).unwrap(), 250);
+        assert_eq!(stake_for_shares(250, 2_500, 1_250), 500);
+        assert!(shares_for_deposit(1, 2_000, 1_000).is_err());
+    }
+
+    #[test]
+    fn exit_fees_split_by_shares() {
+        assert_eq!(fee_share(250, 1_000, 1_000), 250);
+        assert_eq!(fee_share(1_000, 999, 1_000), 999);
+        assert_eq!(fee_share(1, 999, 1_000), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// Logarithmic market scoring rule: C(q) = b * ln(sum_i exp(q_i / b)).
+// Buying d shares of outcome i costs C(q + d*e_i) - C(q), every winning share redeems for 1 lamport,
+// and the market maker's worst case loss is b * ln(n), which the creator funds up front.
+// All math is u128 fixed point with SCALE = 1e12 so it runs in BPF without floats.
+pub const SCALE: u128 = 1_000_000_000_000;
+const LN2: u128 = 693_147_180_560; // ln(2) * SCALE
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Lmsr {
+    pub b: u64,           // liquidity parameter, in lamports
+    pub shares: Vec<u64>, // outstanding shares per outcome (q)
+    pub funding: u64,     // lamports the creator put in to cover b * ln(n)
+}
+
+impl Lmsr {
+    pub const SIZE: usize = 8 + 4 + 2 * 8 + 8; // markets have two outcomes
+
+    pub fn new(b: u64, outcomes: usize) -> Result<Self> {
+        require!(b > 0, ErrorCode::InvalidLiquidity);
+        let shares = vec![0; outcomes];
+        let funding = ceil_div(cost(&shares, b)?, SCALE)?;
+        Ok(Lmsr { b, shares, funding })
+    }
+
+    // lamports a buyer pays for `amount` shares of `outcome`, rounded up in the pool's favour
+    pub fn buy_cost(&self, outcome: usize, amount: u64) -> Result<u64> {
+        let mut after = self.shares.clone();
+        after[outcome] = after[outcome].checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        let delta = cost(&after, self.b)? - cost(&self.shares, self.b)?;
+        ceil_div(delta, SCALE)
+    }
+
+    // lamports a seller receives for `amount` shares of `outcome`, rounded down in the pool's favour
+    pub fn sell_refund(&self, outcome: usize, amount: u64) -> Result<u64> {
+        let mut after = self.shares.clone();
+        after[outcome] = after[outcome].checked_sub(amount).ok_or(ErrorCode::InsufficientShares)?;
+        let delta = cost(&self.shares, self.b)?.saturating_sub(cost(&after, self.b)?);
+        u64::try_from(delta / SCALE).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+
+    // instantaneous price of `outcome` scaled by SCALE, i.e. the implied probability
+    pub fn price(&self, outcome: usize) -> Result<u128> {
+        let ratios = ratios(&self.shares, self.b)?;
+        let max = *ratios.iter().max().unwrap_or(&0);
+        let mut sum = 0u128;
+        for r in &ratios {
+            sum += exp_neg(max - r);
+        }
+        Ok(exp_neg(max - ratios[outcome]) * SCALE / sum)
+    }
+}
+
+// C(q) in lamports * SCALE
+fn cost(shares: &[u64], b: u64) -> Result<u128> {
+    let ratios = ratios(shares, b)?;
+    let max = *ratios.iter().max().unwrap_or(&0);
+    // log-sum-exp: every exponent is <= 0 so exp stays in (0, 1]
+    let mut sum = 0u128;
+    for r in &ratios {
+        sum += exp_neg(max - r);
+    }
+    (b as u128)
+        .checked_mul(max + ln(sum))
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))
+}
+
+fn ratios(shares: &[u64], b: u64) -> Result<Vec<u128>> {
+    require!(b > 0, ErrorCode::InvalidLiquidity);
+    Ok(shares.iter().map(|q| *q as u128 * SCALE / b as u128).collect())
+}
+
+fn ceil_div(value: u128, divisor: u128) -> Result<u64> {
+    u64::try_from(value.div_ceil(divisor)).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+// exp(-x) for fixed point x >= 0: exp(-x) = 2^-k * exp(-r) with x = k * ln2 + r, r in [0, ln2)
+fn exp_neg(x: u128) -> u128 {
+    let k = x / LN2;
+    if k >= 64 {
+        return 0;
+    }
+    let r = x - k * LN2;
+
+    // alternating Taylor series, r < 0.7 so 20 terms is far below 1 / SCALE
+    let mut term = SCALE;
+    let mut sum = SCALE as i128;
+    for n in 1..=20u128 {
+        term = term * r / (n * SCALE);
+        if n % 2 == 1 {
+            sum -= term as i128;
+        } else {
+            sum += term as i128;
+        }
+    }
+    (sum as u128) >> k
+}
+
+// ln(x) for fixed point x >= 1: ln(x) = k * ln2 + ln(m) with x = 2^k * m, m in [1, 2)
+fn ln(x: u128) -> u128 {
+    let mut k = 0u128;
+    let mut m = x;
+    while m >= 2 * SCALE {
+        m >>= 1;
+        k += 1;
+    }
+
+    // ln(m) = 2 * atanh(z) = 2 * (z + z^3/3 + z^5/5 + ...), z = (m - 1) / (m + 1) < 1/3
+    let z = (m - SCALE) * SCALE / (m + SCALE);
+    let z2 = z * z / SCALE;
+    let mut power = z;
+    let mut sum = 0u128;
+    for n in 0..15u128 {
+        sum += power / (2 * n + 1);
+        power = power * z2 / SCALE;
+    }
+    k * LN2 + 2 * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn to_f64(x: u128) -> f64 {
+        x as f64 / SCALE as f64
+    }
+
+    #[test]
+    fn exp_and_ln_match_floats() {
+        for x in [0.0, 0.001, 0.5, 0.693, 1.0, 2.5, 10.0, 30.0] {
+            let fixed = exp_neg((x * SCALE as f64) as u128);
+            assert!((to_f64(fixed) - (-x).exp()).abs() < 1e-9, "exp(-{x})");
+        }
+        for x in [1.0, 1.0001, 1.5, 2.0, 3.7, 100.0, 12345.678] {
+            let fixed = ln((x * SCALE as f64) as u128);
+            assert!((to_f64(fixed) - x.ln()).abs() < 1e-9, "ln({x})");
+        }
+    }
+
+    #[test]
+    fn funding_covers_worst_case_loss() {
+        let lmsr = Lmsr::new(100 * SOL, 2).unwrap();
+        // b * ln(2) ~= 69.31 SOL
+        assert_eq!(lmsr.funding, 69_314_718_056);
+        assert_eq!(lmsr.price(0).unwrap(), SCALE / 2);
+    }
+
+    #[test]
+    fn buying_moves_the_price_and_selling_back_never_profits() {
+        let mut lmsr = Lmsr::new(100 * SOL, 2).unwrap();
+        let cost = lmsr.buy_cost(0, 50 * SOL).unwrap();
+        // 50 shares at a starting price of 0.5 cost more than 25 SOL but less than 50
+        assert!(cost > 25 * SOL && cost < 50 * SOL);
+
+        lmsr.shares[0] += 50 * SOL;
+        assert!(lmsr.price(0).unwrap() > SCALE / 2);
+        assert!(lmsr.price(0).unwrap() + lmsr.price(1).unwrap() <= SCALE);
+
+        let refund = lmsr.sell_refund(0, 50 * SOL).unwrap();
+        assert!(refund <= cost);
+        assert!(cost - refund <= 1);
+    }
+
+    #[test]
+    fn pool_stays_solvent_for_any_winner() {
+        let mut lmsr = Lmsr::new(10 * SOL, 2).unwrap();
+        let mut collected = lmsr.funding;
+        for (outcome, amount) in [(0, 3 * SOL), (1, 40 * SOL), (0, 7 * SOL), (1, 1)] {
+            collected += lmsr.buy_cost(outcome, amount).unwrap();
+            lmsr.shares[outcome] += amount;
+        }
+        assert!(collected >= lmsr.shares[0]);
+        assert!(collected >= lmsr.shares[1]);
+    }
+
+    #[test]
+    fn cannot_sell_more_than_outstanding() {
+        let lmsr = Lmsr::new(SOL, 2).unwrap();
+        assert!(lmsr.sell_refund(0, 1).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+pub const MAX_OUTCOME_LEN: usize = 50;
+pub const MAX_TAGS: usize = 5;
+pub const MAX_TAG_LEN: usize = 20;
+pub const MAX_RESOLUTION_SOURCE_LEN: usize = 200;
+pub const MAX_URI_LEN: usize = 200;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarketCategory {
+    Crypto,
+    Sports,
+    Politics,
+    Economics,
+    Entertainment,
+    Science,
+    Other,
+}
+
+// what a front-end needs to list a market; the full description lives off-chain at `uri`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MarketMetadata {
+    pub category: MarketCategory,
+    pub tags: Vec<String>,
+    pub resolution_source: String, // e.g. "Official CoinGecko SOL/USD close"
+    pub uri: String,
+    pub content_hash: [u8; 32], // sha256 of the document at `uri`, so edits off-chain are detectable
+}
+
+impl MarketMetadata {
+    pub const SIZE: usize = 1
+        + 4 + MAX_TAGS * (4 + MAX_TAG_LEN)
+        + 4 + MAX_RESOLUTION_SOURCE_LEN
+        + 4 + MAX_URI_LEN
+        + 32;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.tags.len() <= MAX_TAGS, ErrorCode::TooManyTags);
+        require!(self.tags.iter().all(|tag| tag.len() <= MAX_TAG_LEN), ErrorCode::TagTooLong);
+        require!(self.resolution_source.len() <= MAX_RESOLUTION_SOURCE_LEN, ErrorCode::ResolutionSourceTooLong);
+        require!(self.uri.len() <= MAX_URI_LEN, ErrorCode::UriTooLong);
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// UMA-style optimistic resolution: anyone proposes an outcome against a bond, anyone can
+// dispute it with the same bond inside the challenge window, and the arbiter settles disputes.
+// A dispute the arbiter has not ruled on by the end of the arbitration window is refunded.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OptimisticConfig {
+    pub arbiter: Pubkey,
+    pub bond: u64,             // lamports posted by both the proposer and the disputer
+    pub challenge_period: i64, // seconds after the proposal during which it can be disputed
+    pub arbitration_period: i64, // seconds after the challenge window the arbiter has to rule
+}
+
+impl OptimisticConfig {
+    pub const SIZE: usize = 32 + 8 + 8 + 8;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.bond > 0, ErrorCode::InvalidOptimisticConfig);
+        require!(self.challenge_period > 0, ErrorCode::InvalidOptimisticConfig);
+        require!(self.arbitration_period > 0, ErrorCode::InvalidOptimisticConfig);
+        Ok(())
+    }
+
+    pub fn challenge_ends_at(&self, proposed_at: i64) -> i64 {
+        proposed_at.saturating_add(self.challenge_period)
+    }
+
+    pub fn arbitration_ends_at(&self, proposed_at: i64) -> i64 {
+        self.challenge_ends_at(proposed_at).saturating_add(self.arbitration_period)
+    }
+}
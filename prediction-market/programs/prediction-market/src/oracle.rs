@@ -0,0 +1,174 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// Price feed account layout (little endian), modeled on Pyth's legacy price account:
+//   0..4   magic        u32 = PRICE_FEED_MAGIC
+//   4..8   expo         i32
+//   8..16  price        i64
+//   16..24 conf         u64
+//   24..32 publish_time i64
+// Anything that writes this layout (a Pyth/Switchboard adapter or a mock feed in tests) can back a market.
+pub const PRICE_FEED_MAGIC: u32 = 0xa1b2_c3d4;
+pub const PRICE_FEED_LEN: usize = 32;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+}
+
+impl Comparison {
+    pub fn holds(&self, price: i64, threshold: i64) -> bool {
+        match self {
+            Comparison::GreaterThan => price > threshold,
+            Comparison::GreaterThanOrEqual => price >= threshold,
+            Comparison::LessThan => price < threshold,
+            Comparison::LessThanOrEqual => price <= threshold,
+        }
+    }
+}
+
+// "Will SOL be above $200?" -> feed = SOL/USD, comparison = GreaterThan, threshold = 200 * 10^-expo
+// outcome 0 wins when the comparison holds, outcome 1 otherwise
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OracleConfig {
+    pub feed: Pubkey,
+    pub comparison: Comparison,
+    pub threshold: i64, // in the feed's own units, i.e. price * 10^-expo
+    pub expo: i32,
+    pub max_staleness: i64, // seconds between publish_time and the resolving transaction
+    pub max_confidence_bps: u16, // conf / |price| upper bound
+}
+
+impl OracleConfig {
+    pub const SIZE: usize = 32 + 1 + 8 + 4 + 8 + 2;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.max_staleness > 0, ErrorCode::InvalidOracleConfig);
+        require!(self.max_confidence_bps <= 10_000, ErrorCode::InvalidOracleConfig);
+        Ok(())
+    }
+
+    // checks the feed against the config and returns the winning outcome index
+    pub fn resolve(&self, feed: &PriceFeed, event_time: i64, now: i64) -> Result<u8> {
+        require!(feed.expo == self.expo, ErrorCode::InvalidPriceFeed);
+        require!(feed.publish_time >= event_time, ErrorCode::PricePublishedTooEarly);
+        require!(now.saturating_sub(feed.publish_time) <= self.max_staleness, ErrorCode::StalePrice);
+
+        let max_conf = feed.price.unsigned_abs() as u128 * self.max_confidence_bps as u128 / 10_000;
+        require!(feed.conf as u128 <= max_conf, ErrorCode::PriceConfidenceTooWide);
+
+        Ok(if self.comparison.holds(feed.price, self.threshold) { 0 } else { 1 })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceFeed {
+    pub expo: i32,
+    pub price: i64,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+impl PriceFeed {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        require!(data.len() >= PRICE_FEED_LEN, ErrorCode::InvalidPriceFeed);
+        let magic = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        require!(magic == PRICE_FEED_MAGIC, ErrorCode::InvalidPriceFeed);
+
+        Ok(PriceFeed {
+            expo: i32::from_le_bytes(data[4..8].try_into().unwrap()),
+            price: i64::from_le_bytes(data[8..16].try_into().unwrap()),
+            conf: u64::from_le_bytes(data[16..24].try_into().unwrap()),
+            publish_time: i64::from_le_bytes(data[24..32].try_into().unwrap()),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; PRICE_FEED_LEN] {
+        let mut data = [0u8; PRICE_FEED_LEN];
+        data[0..4].copy_from_slice(&PRICE_FEED_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&self.expo.to_le_bytes());
+        data[8..16].copy_from_slice(&self.price.to_le_bytes());
+        data[16..24].copy_from_slice(&self.conf.to_le_bytes());
+        data[24..32].copy_from_slice(&self.publish_time.to_le_bytes());
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT_TIME: i64 = 1_800_000_000;
+
+    fn sol_above_200() -> OracleConfig {
+        OracleConfig {
+            feed: Pubkey::new_unique(),
+            comparison: Comparison::GreaterThan,
+            threshold: 200_00000000,
+            expo: -8,
+            max_staleness: 60,
+            max_confidence_bps: 100,
+        }
+    }
+
+    fn mock_feed(price: i64, conf: u64, publish_time: i64) -> Vec<u8> {
+        PriceFeed { expo: -8, price, conf, publish_time }.to_bytes().to_vec()
+    }
+
+    #[test]
+    fn parses_mock_feed_layout() {
+        let data = mock_feed(201_50000000, 10_000_000, EVENT_TIME);
+        let feed = PriceFeed::parse(&data).unwrap();
+        assert_eq!(feed.price, 201_50000000);
+        assert_eq!(feed.conf, 10_000_000);
+        assert_eq!(feed.expo, -8);
+        assert_eq!(feed.publish_time, EVENT_TIME);
+    }
+
+    #[test]
+    fn rejects_short_or_foreign_accounts() {
+        let data = mock_feed(1, 0, EVENT_TIME);
+        assert!(PriceFeed::parse(&data[..PRICE_FEED_LEN - 1]).is_err());
+
+        let mut foreign = data.clone();
+        foreign[0] ^= 0xff;
+        assert!(PriceFeed::parse(&foreign).is_err());
+    }
+
+    #[test]
+    fn resolves_yes_and_no() {
+        let config = sol_above_200();
+        let above = PriceFeed::parse(&mock_feed(201_00000000, 0, EVENT_TIME + 5)).unwrap();
+        let below = PriceFeed::parse(&mock_feed(199_00000000, 0, EVENT_TIME + 5)).unwrap();
+        let equal = PriceFeed::parse(&mock_feed(200_00000000, 0, EVENT_TIME + 5)).unwrap();
+
+        assert_eq!(config.resolve(&above, EVENT_TIME, EVENT_TIME + 10).unwrap(), 0);
+        assert_eq!(config.resolve(&below, EVENT_TIME, EVENT_TIME + 10).unwrap(), 1);
+        assert_eq!(config.resolve(&equal, EVENT_TIME, EVENT_TIME + 10).unwrap(), 1);
+
+        let inclusive = OracleConfig { comparison: Comparison::GreaterThanOrEqual, ..config };
+        assert_eq!(inclusive.resolve(&equal, EVENT_TIME, EVENT_TIME + 10).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_stale_early_and_wide_prices() {
+        let config = sol_above_200();
+
+        let stale = PriceFeed::parse(&mock_feed(201_00000000, 0, EVENT_TIME)).unwrap();
+        assert!(config.resolve(&stale, EVENT_TIME, EVENT_TIME + 61).is_err());
+
+        let early = PriceFeed::parse(&mock_feed(201_00000000, 0, EVENT_TIME - 1)).unwrap();
+        assert!(config.resolve(&early, EVENT_TIME, EVENT_TIME).is_err());
+
+        // 1% of 201 is 2.01, so a 3.00 confidence interval is too wide
+        let wide = PriceFeed::parse(&mock_feed(201_00000000, 3_00000000, EVENT_TIME)).unwrap();
+        assert!(config.resolve(&wide, EVENT_TIME, EVENT_TIME).is_err());
+
+        let other_expo = PriceFeed { expo: -6, ..stale };
+        assert!(config.resolve(&other_expo, EVENT_TIME, EVENT_TIME).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// Prices are in basis points of the 1 lamport a winning share pays out, and quantities
+// trade in lots of PRICE_SCALE shares so `quantity / PRICE_SCALE * price` is always exact.
+pub const PRICE_SCALE: u64 = 10_000;
+pub const MAX_ORDERS: usize = 64;
+pub const MIN_ORDER_VALUE: u64 = 10_000_000; // 0.01 SOL, so filling a slab isn't free
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+#[zero_copy]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Order {
+    pub owner: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub seq: u64, // placement order, earlier orders are makers and win ties
+}
+
+// one book per market outcome; bid and ask slabs are fixed arrays with the live
+// orders packed at the front
+#[account(zero_copy)]
+pub struct OrderBook {
+    pub market: Pubkey,
+    // the outcome token traded on this book
+    pub mint: Pubkey,
+    pub next_seq: u64,
+    pub bid_count: u32,
+    pub ask_count: u32,
+    pub outcome: u8,
+    pub _padding: [u8; 7],
+    pub bids: [Order; MAX_ORDERS],
+    pub asks: [Order; MAX_ORDERS],
+}
+
+// lamports a bid for `quantity` shares at `price` has to lock up
+pub fn order_value(quantity: u64, price: u64) -> Result<u64> {
+    (quantity / PRICE_SCALE)
+        .checked_mul(price)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))
+}
+
+impl OrderBook {
+    pub fn insert(&mut self, side: Side, owner: Pubkey, price: u64, quantity: u64) -> Result<u64> {
+        require!(price > 0 && price < PRICE_SCALE, ErrorCode::InvalidOrderPrice);
+        require!(quantity >= PRICE_SCALE && quantity / PRICE_SCALE * PRICE_SCALE == quantity, ErrorCode::InvalidOrderQuantity);
+
+        let seq = self.next_seq;
+        let order = Order { owner, price, quantity, seq };
+        let (slab, count) = self.slab_mut(side);
+        require!((*count as usize) < MAX_ORDERS, ErrorCode::OrderBookFull);
+        slab[*count as usize] = order;
+        *count += 1;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+
+    pub fn remove(&mut self, side: Side, index: usize) -> Order {
+        let (slab, count) = self.slab_mut(side);
+        let order = slab[index];
+        let last = *count as usize - 1;
+        slab[index] = slab[last];
+        slab[last] = Order { owner: Pubkey::default(), price: 0, quantity: 0, seq: 0 };
+        *count -= 1;
+        order
+    }
+
+    pub fn find(&self, side: Side, seq: u64) -> Option<usize> {
+        self.orders(side).iter().position(|order| order.seq == seq)
+    }
+
+    // highest bid, earliest first on ties
+    pub fn best_bid(&self) -> Option<usize> {
+        (0..self.bid_count as usize).max_by(|&a, &b| {
+            let (a, b) = (&self.bids[a], &self.bids[b]);
+            a.price.cmp(&b.price).then(b.seq.cmp(&a.seq))
+        })
+    }
+
+    // lowest ask, earliest first on ties
+    pub fn best_ask(&self) -> Option<usize> {
+        (0..self.ask_count as usize).min_by(|&a, &b| {
+            let (a, b) = (&self.asks[a], &self.asks[b]);
+            a.price.cmp(&b.price).then(a.seq.cmp(&b.seq))
+        })
+    }
+
+    pub fn orders(&self, side: Side) -> &[Order] {
+        match side {
+            Side::Bid => &self.bids[..self.bid_count as usize],
+            Side::Ask => &self.asks[..self.ask_count as usize],
+        }
+    }
+
+    pub fn orders_mut(&mut self, side: Side) -> &mut [Order] {
+        match side {
+            Side::Bid => &mut self.bids[..self.bid_count as usize],
+            Side::Ask => &mut self.asks[..self.ask_count as usize],
+        }
+    }
+
+    fn slab_mut(&mut self, side: Side) -> (&mut [Order; MAX_ORDERS], &mut u32) {
+        match side {
+            Side::Bid => (&mut self.bids, &mut self.bid_count),
+            Side::Ask => (&mut self.asks, &mut self.ask_count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_book() -> Box<OrderBook> {
+        Box::new(bytemuck::Zeroable::zeroed())
+    }
+
+    #[test]
+    fn best_prices_prefer_earlier_orders() {
+        let mut book = empty_book();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        book.insert(Side::Bid, alice, 4_000, 10_000).unwrap();
+        book.insert(Side::Bid, bob, 6_000, 10_000).unwrap();
+        book.insert(Side::Bid, alice, 6_000, 20_000).unwrap();
+        book.insert(Side::Ask, bob, 7_000, 10_000).unwrap();
+        book.insert(Side::Ask, alice, 6_500, 10_000).unwrap();
+
+        assert_eq!(book.bids[book.best_bid().unwrap()].owner, bob);
+        assert_eq!(book.asks[book.best_ask().unwrap()].owner, alice);
+    }
+
+    #[test]
+    fn remove_keeps_the_slab_packed() {
+        let mut book = empty_book();
+        let owner = Pubkey::new_unique();
+        for price in [1_000, 2_000, 3_000] {
+            book.insert(Side::Ask, owner, price, PRICE_SCALE).unwrap();
+        }
+        let removed = book.remove(Side::Ask, book.find(Side::Ask, 0).unwrap());
+        assert_eq!(removed.price, 1_000);
+        assert_eq!(book.orders(Side::Ask).len(), 2);
+        assert_eq!(book.asks[book.best_ask().unwrap()].price, 2_000);
+        assert!(book.find(Side::Ask, 0).is_none());
+    }
+
+    #[test]
+    fn rejects_bad_orders_and_full_slabs() {
+        let mut book = empty_book();
+        let owner = Pubkey::new_unique();
+        assert!(book.insert(Side::Bid, owner, PRICE_SCALE, PRICE_SCALE).is_err());
+        assert!(book.insert(Side::Bid, owner, 5_000, PRICE_SCALE + 1).is_err());
+        for _ in 0..MAX_ORDERS {
+            book.insert(Side::Bid, owner, 5_000, PRICE_SCALE).unwrap();
+        }
+        assert!(book.insert(Side::Bid, owner, 5_000, PRICE_SCALE).is_err());
+    }
+
+    #[test]
+    fn order_value_is_exact_per_lot() {
+        assert_eq!(order_value(30_000, 2_500).unwrap(), 7_500);
+    }
+}
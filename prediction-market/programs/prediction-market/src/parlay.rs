@@ -0,0 +1,122 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+pub const MAX_LEGS: usize = 5;
+pub const ODDS_SCALE: u64 = 10_000; // decimal odds in bps, 2.5x = 25_000
+pub const HOUSE_EDGE_BPS: u64 = 500;
+pub const MAX_LEG_ODDS: u64 = 10 * ODDS_SCALE; // long shots on thin pools are priced at 10x at most
+pub const MIN_STAKE: u64 = 10_000_000; // 0.01 SOL, an open parlay locks withdrawals on its legs' markets
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParlayLeg {
+    pub market: Pubkey,
+    pub outcome: u8,
+    pub odds: u64, // fixed when the parlay is placed, in ODDS_SCALE
+}
+
+impl ParlayLeg {
+    pub const SIZE: usize = 32 + 1 + 8;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LegResult {
+    Pending,
+    Won,
+    Lost,
+    Cancelled, // the leg's market became refundable, so it counts as even odds
+}
+
+// pari-mutuel odds an outcome pays right now: the whole pool over what is staked on it, capped
+// at MAX_LEG_ODDS
+pub fn leg_odds(total_bets: &[u64], outcome: u8) -> Result<u64> {
+    let on_outcome = total_bets[outcome as usize];
+    require!(on_outcome > 0, ErrorCode::NoOddsAvailable);
+    let pool: u64 = total_bets.iter().sum();
+    let odds = pool as u128 * ODDS_SCALE as u128 / on_outcome as u128;
+    Ok(odds.min(MAX_LEG_ODDS as u128) as u64)
+}
+
+// stake times every leg's odds, less the house edge
+pub fn potential_payout(stake: u64, odds: impl IntoIterator<Item = u64>) -> Result<u64> {
+    let mut payout = stake as u128;
+    for leg_odds in odds {
+        payout = payout * leg_odds as u128 / ODDS_SCALE as u128;
+    }
+    let payout = payout * (10_000 - HOUSE_EDGE_BPS) as u128 / 10_000;
+    u64::try_from(payout).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+// what the house holds back for a parlay: enough for a win, or for the stake if every leg is cancelled
+pub fn reserve(stake: u64, potential_payout: u64) -> u64 {
+    stake.max(potential_payout)
+}
+
+// None while a leg is still open; a lost leg settles the parlay at zero straight away.
+// Cancelled legs drop out, and a parlay whose legs were all cancelled gets its stake back.
+pub fn settle(stake: u64, legs: &[ParlayLeg], results: &[LegResult]) -> Result<Option<u64>> {
+    if results.contains(&LegResult::Lost) {
+        return Ok(Some(0));
+    }
+    if results.contains(&LegResult::Pending) {
+        return Ok(None);
+    }
+    if results.iter().all(|result| *result == LegResult::Cancelled) {
+        return Ok(Some(stake));
+    }
+    let won = legs.iter().zip(results).filter(|(_, result)| **result == LegResult::Won).map(|(leg, _)| leg.odds);
+    potential_payout(stake, won).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(odds: u64) -> ParlayLeg {
+        ParlayLeg { market: Pubkey::new_unique(), outcome: 0, odds }
+    }
+
+    #[test]
+    fn odds_come_from_the_pool() {
+        assert_eq!(leg_odds(&[250, 750], 0).unwrap(), 40_000);
+        assert_eq!(leg_odds(&[250, 750], 1).unwrap(), 13_333);
+        assert!(leg_odds(&[0, 750], 0).is_err());
+    }
+
+    #[test]
+    fn odds_are_capped() {
+        assert_eq!(leg_odds(&[1, 1_000_000], 0).unwrap(), MAX_LEG_ODDS);
+    }
+
+    #[test]
+    fn payout_multiplies_odds_less_the_edge() {
+        // 1_000 at 2x and 3x is 6_000, less 5%
+        assert_eq!(potential_payout(1_000, [20_000, 30_000]).unwrap(), 5_700);
+    }
+
+    #[test]
+    fn settles_lazily() {
+        let legs = [leg(20_000), leg(30_000)];
+        assert_eq!(settle(1_000, &legs, &[LegResult::Won, LegResult::Pending]).unwrap(), None);
+        assert_eq!(settle(1_000, &legs, &[LegResult::Lost, LegResult::Pending]).unwrap(), Some(0));
+        assert_eq!(settle(1_000, &legs, &[LegResult::Won, LegResult::Won]).unwrap(), Some(5_700));
+    }
+
+    #[test]
+    fn cancelled_legs_are_refunded() {
+        let legs = [leg(20_000), leg(30_000)];
+        assert_eq!(settle(1_000, &legs, &[LegResult::Won, LegResult::Cancelled]).unwrap(), Some(1_900));
+        assert_eq!(settle(1_000, &legs, &[LegResult::Cancelled, LegResult::Cancelled]).unwrap(), Some(1_000));
+    }
+
+    #[test]
+    fn reserve_covers_every_settlement() {
+        // near-even legs pay less than the stake once the edge is taken, a full cancel refunds it
+        let legs = [leg(10_100), leg(10_100)];
+        let payout = potential_payout(1_000, legs.iter().map(|leg| leg.odds)).unwrap();
+        assert!(payout < 1_000);
+        let cancelled = settle(1_000, &legs, &[LegResult::Cancelled, LegResult::Cancelled]).unwrap().unwrap();
+        assert!(cancelled <= reserve(1_000, payout));
+        assert_eq!(reserve(1_000, 5_700), 5_700);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::scalar;
+
+// Pure payout engine shared by claim_payout, claim_many and remove_liquidity. Every payout is
+// rounded down, so the winners (LPs included) never take more than the pool plus exit fees; the
+// rounding dust left behind is `pool - paid_out` once everyone has claimed.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Settlement {
+    Winner(u8),
+    Scalar { min: i64, max: i64, value: i64 },
+}
+
+// everything claims are paid from: the bets on each outcome plus exit fees left behind
+pub fn pool(total_bets: &[u64], exit_fees: u64) -> u64 {
+    total_bets.iter().sum::<u64>() + exit_fees
+}
+
+// what `amount` staked on `outcome` is owed once the market settled
+pub fn payout(settlement: Settlement, total_bets: &[u64], exit_fees: u64, outcome: u8, amount: u64) -> u64 {
+    match settlement {
+        Settlement::Winner(winning_outcome) => {
+            let total_winning = total_bets[winning_outcome as usize];
+            if outcome != winning_outcome || total_winning == 0 {
+                return 0;
+            }
+            (amount as u128 * pool(total_bets, exit_fees) as u128 / total_winning as u128) as u64
+        }
+        Settlement::Scalar { min, max, value } => {
+            let (total_long, total_short) = (total_bets[scalar::LONG as usize], total_bets[scalar::SHORT as usize]);
+            let (long_pool, short_pool) = scalar::side_pools(min, max, value, total_long, total_short);
+            let (side_pool, side_total) = if outcome == scalar::LONG {
+                (long_pool, total_long)
+            } else {
+                (short_pool, total_short)
+            };
+            if side_total == 0 {
+                return 0;
+            }
+            // exit fees are shared out in the same proportion as the pool
+            let bets = (total_long + total_short) as u128;
+            let side_pool = side_pool as u128 * (bets + exit_fees as u128) / bets;
+            (amount as u128 * side_pool / side_total as u128) as u64
+        }
+    }
+}
+
+// what is still owed to, or left over after, the claims paid so far; all of it is dust once
+// every winner has claimed
+pub fn unclaimed(total_bets: &[u64], exit_fees: u64, paid_out: u64) -> u64 {
+    pool(total_bets, exit_fees).saturating_sub(paid_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // bets as (outcome, amount) with totals that match them, like Market.total_bets
+    fn totals(bets: &[(u8, u64)]) -> Vec<u64> {
+        let mut totals = vec![0; 2];
+        for (outcome, amount) in bets {
+            totals[*outcome as usize] += amount;
+        }
+        totals
+    }
+
+    fn settlement() -> impl Strategy<Value = Settlement> {
+        prop_oneof![
+            (0u8..2).prop_map(Settlement::Winner),
+            (-1_000i64..1_000, 1i64..1_000, -3_000i64..3_000)
+                .prop_map(|(min, width, value)| Settlement::Scalar { min, max: min + width, value }),
+        ]
+    }
+
+    fn bets() -> impl Strategy<Value = Vec<(u8, u64)>> {
+        prop::collection::vec((0u8..2, 1u64..1_000_000_000_000), 1..40)
+    }
+
+    proptest! {
+        #[test]
+        fn payouts_never_exceed_the_pool(bets in bets(), exit_fees in 0u64..1_000_000_000, settlement in settlement()) {
+            let total_bets = totals(&bets);
+            let paid_out: u64 = bets.iter().map(|(outcome, amount)| payout(settlement, &total_bets, exit_fees, *outcome, *amount)).sum();
+            prop_assert!(paid_out <= pool(&total_bets, exit_fees));
+        }
+
+        #[test]
+        fn dust_is_bounded_and_sweepable(bets in bets(), exit_fees in 0u64..1_000_000_000, settlement in settlement()) {
+            let total_bets = totals(&bets);
+            let payouts: Vec<u64> = bets.iter().map(|(outcome, amount)| payout(settlement, &total_bets, exit_fees, *outcome, *amount)).collect();
+            let paid_out: u64 = payouts.iter().sum();
+            let dust = unclaimed(&total_bets, exit_fees, paid_out);
+
+            prop_assert_eq!(paid_out + dust, pool(&total_bets, exit_fees));
+            match settlement {
+                // nobody backed the winner, so the whole pool is left to sweep
+                Settlement::Winner(winner) if total_bets[winner as usize] == 0 => prop_assert_eq!(paid_out, 0),
+                // each claim rounds down by less than a lamport, scalar sides round once more each
+                _ => prop_assert!(dust <= bets.len() as u64 + 2),
+            }
+        }
+
+        #[test]
+        fn losing_bets_get_nothing(bets in bets(), winner in 0u8..2) {
+            let total_bets = totals(&bets);
+            for (outcome, amount) in bets.iter().filter(|(outcome, _)| *outcome != winner) {
+                prop_assert_eq!(payout(Settlement::Winner(winner), &total_bets, 0, *outcome, *amount), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn splits_the_pool_pro_rata() {
+        let total_bets = [300, 600];
+        assert_eq!(payout(Settlement::Winner(0), &total_bets, 0, 0, 100), 300);
+        assert_eq!(payout(Settlement::Winner(0), &total_bets, 90, 0, 100), 330);
+        assert_eq!(payout(Settlement::Winner(0), &total_bets, 0, 1, 600), 0);
+    }
+
+    #[test]
+    fn rounding_leaves_dust_behind() {
+        let total_bets = [3, 7];
+        let paid_out = 3 * payout(Settlement::Winner(0), &total_bets, 0, 0, 1);
+        assert_eq!(paid_out, 9);
+        assert_eq!(unclaimed(&total_bets, 0, paid_out), 1);
+    }
+}
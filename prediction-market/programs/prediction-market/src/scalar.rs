@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+pub const LONG: u8 = 0;
+pub const SHORT: u8 = 1;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarketKind {
+    Binary,
+    // the answer is a number in [min, max]; outcome 0 is LONG, outcome 1 is SHORT
+    Scalar { min: i64, max: i64 },
+}
+
+impl MarketKind {
+    pub const SIZE: usize = 1 + 8 + 8;
+
+    pub fn validate(&self) -> Result<()> {
+        if let MarketKind::Scalar { min, max } = self {
+            require!(min < max, ErrorCode::InvalidScalarRange);
+        }
+        Ok(())
+    }
+}
+
+// splits the pool between the LONG and SHORT sides in proportion to where `value` lands in [min, max].
+// A side nobody bet on gets nothing, so the other side takes the whole pool instead of leaving it stranded.
+pub fn side_pools(min: i64, max: i64, value: i64, total_long: u64, total_short: u64) -> (u64, u64) {
+    let total_pool = total_long + total_short;
+    if total_long == 0 {
+        return (0, total_pool);
+    }
+    if total_short == 0 {
+        return (total_pool, 0);
+    }
+
+    let value = value.clamp(min, max);
+    let long_pool = (total_pool as u128 * (value as i128 - min as i128) as u128 / (max as i128 - min as i128) as u128) as u64;
+    (long_pool, total_pool - long_pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_linearly_inside_the_range() {
+        assert_eq!(side_pools(0, 100, 25, 60, 40), (25, 75));
+        assert_eq!(side_pools(-50, 50, 0, 10, 10), (10, 10));
+        assert_eq!(side_pools(0, 3, 1, 50, 50), (33, 67));
+    }
+
+    #[test]
+    fn clamps_values_outside_the_range() {
+        assert_eq!(side_pools(0, 100, 250, 60, 40), (100, 0));
+        assert_eq!(side_pools(0, 100, -5, 60, 40), (0, 100));
+    }
+
+    #[test]
+    fn empty_side_forfeits_to_the_other() {
+        assert_eq!(side_pools(0, 100, 90, 0, 40), (0, 40));
+        assert_eq!(side_pools(0, 100, 10, 60, 0), (60, 0));
+    }
+}
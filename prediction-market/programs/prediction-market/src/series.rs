@@ -0,0 +1,50 @@
+pub const MAX_SERIES_NAME_LEN: usize = 32;
+pub const ROUND_PLACEHOLDER: &str = "{round}";
+const MAX_ROUND_DIGITS: usize = 20; // u64::MAX
+
+// "BTC up or down in round {round}?" -> "BTC up or down in round 42?"
+pub fn round_question(question_format: &str, round: u64) -> String {
+    question_format.replace(ROUND_PLACEHOLDER, &round.to_string())
+}
+
+// the longest question the format can expand to, whatever the round number
+pub fn max_question_len(question_format: &str) -> usize {
+    let placeholders = question_format.matches(ROUND_PLACEHOLDER).count();
+    question_format.len() + placeholders * (MAX_ROUND_DIGITS - ROUND_PLACEHOLDER.len())
+}
+
+// rounds run back to back, `duration` seconds each. A crank that comes late skips the rounds
+// whose betting window already closed, so it returns the round to spawn and when it opened.
+pub fn current_round(round: u64, opens_at: i64, duration: i64, now: i64) -> (u64, i64) {
+    if now < opens_at + duration {
+        return (round, opens_at);
+    }
+    let missed = (now - opens_at) / duration;
+    (round + missed as u64, opens_at + missed * duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_round_number() {
+        assert_eq!(round_question("BTC up or down, round {round}?", 7), "BTC up or down, round 7?");
+        assert_eq!(round_question("Same question every day", 7), "Same question every day");
+    }
+
+    #[test]
+    fn every_placeholder_can_take_the_widest_round_number() {
+        let format = "{round}{round}";
+        assert_eq!(max_question_len(format), 40);
+        assert_eq!(round_question(format, u64::MAX).len(), max_question_len(format));
+        assert_eq!(max_question_len("Same question every day"), 23);
+    }
+
+    #[test]
+    fn late_cranks_skip_closed_rounds() {
+        assert_eq!(current_round(3, 1_000, 100, 1_050), (3, 1_000));
+        assert_eq!(current_round(3, 1_000, 100, 1_100), (4, 1_100));
+        assert_eq!(current_round(3, 1_000, 100, 1_350), (6, 1_300));
+    }
+}
@@ -4,6 +4,15 @@ import { PredictionMarket } from "../target/types/prediction_market";
 import { publicKey } from "@coral-xyz/anchor/dist/cjs/utils";
 import { PublicKey, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
 import { BN } from "bn.js";
+import {
+  getAssociatedTokenAddressSync,
+  createMint,
+  createAssociatedTokenAccount,
+  mintTo,
+  closeAccount,
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
 import { assert } from "chai";
 
 describe("prediction-market", () => {
@@ -15,9 +24,46 @@ describe("prediction-market", () => {
   const bettor1 = anchor.web3.Keypair.generate();
   const bettor2 = anchor.web3.Keypair.generate();
 
+  // SOL markets leave the SPL collateral accounts empty
+  const solCollateral = { collateralMint: null, vault: null, tokenProgram: null };
+  const solBetAccounts = { vault: null, bettorToken: null, tokenProgram: null };
+
+  const defaultMetadata = {
+    category: { other: {} },
+    tags: [],
+    resolutionSource: "",
+    uri: "",
+    contentHash: Array(32).fill(0),
+  };
+
+  const treasury = anchor.web3.Keypair.generate();
+  const creationFee = new BN(0.01 * LAMPORTS_PER_SOL);
+  const claimWindow = new BN(24 * 60 * 60); // the shortest the program allows
+  const [factoryPDA] = PublicKey.findProgramAddressSync([Buffer.from("factory")], program.programId);
+  const [otherIndexPDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("category_index"), Buffer.from([6])], // MarketCategory::Other
+    program.programId
+  );
+  const factoryAccounts = {
+    factory: factoryPDA,
+    treasury: treasury.publicKey,
+    categoryIndex: otherIndexPDA,
+    creatorAllowance: null,
+  };
+
+  const standalone = { parentMarket: null }; // not a conditional market
+  const binary = { binary: {} };
+  const noLimits = { minBet: new BN(0), maxBetPerBettor: new BN(0), maxOutcomeShareBps: 0, allowlistRoot: null };
+
+  // markets are seeded by the factory counter, so remember which id each question got
+  const marketIds = new Map<string, BN>();
   const getMarketPDA = async (question: string) => {
+    if (!marketIds.has(question)) {
+      const factory = await program.account.marketFactory.fetch(factoryPDA);
+      marketIds.set(question, factory.marketCount);
+    }
     return PublicKey.findProgramAddressSync(
-      [Buffer.from("market"), authority.publicKey.toBuffer(), Buffer.from(question)],
+      [Buffer.from("market"), marketIds.get(question).toArrayLike(Buffer, "le", 8)],
       program.programId
     )
   }
@@ -35,6 +81,17 @@ describe("prediction-market", () => {
     await provider.connection.requestAirdrop(bettor2.publicKey, 2 * LAMPORTS_PER_SOL)
 
     await new Promise((resolve) => setTimeout(resolve, 2000))
+
+    await program.methods
+      .initializeFactory(creationFee, false, claimWindow)
+      .accounts({ factory: factoryPDA, admin: authority.publicKey, treasury: treasury.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .initCategoryIndex({ other: {} })
+      .accounts({ factory: factoryPDA, admin: authority.publicKey, categoryIndex: otherIndexPDA, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
   })
 
   it("Creating a market", async () => {
@@ -45,8 +102,9 @@ describe("prediction-market", () => {
     const [marketPDA, marketBump] = await getMarketPDA(question);
 
     const tx = await program.methods
-      .createMarket(question, outcomes, resolutionTime)
+      .createMarket(question, outcomes, resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
       .accounts({
+        ...solCollateral, ...factoryAccounts, ...standalone,
         market: marketPDA,
         authority: authority.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
@@ -59,8 +117,8 @@ describe("prediction-market", () => {
     assert.equal(marketAccount.authority.toBase58(), authority.publicKey.toBase58(), "Authority should match")
     assert.deepEqual(marketAccount.outcomes, outcomes, "Outcomes should match");
     assert.isTrue(
-      marketAccount.resolutionTime.eq(resolutionTime),
-      "The resolution time should match"
+      marketAccount.eventTime.eq(resolutionTime),
+      "The event time should match"
     );
     assert.isFalse(marketAccount.resolved, "Should be false")
     assert.isTrue(marketAccount.isActive, "Market should be active")
@@ -83,8 +141,9 @@ describe("prediction-market", () => {
     // Place bet for bettor1
     console.log("Placing bet for bettor1...");
     await program.methods
-      .placeBet(outcomeIndex1, betAmount1)
+      .placeBet(outcomeIndex1, betAmount1, [])
       .accounts({
+        ...solBetAccounts,
         market: marketPDA,
         bettor: bettor1.publicKey,
         bet: betPDA1,
@@ -97,8 +156,9 @@ describe("prediction-market", () => {
     // Place bet for bettor2
     console.log("Placing bet for bettor2...");
     await program.methods
-      .placeBet(outcomeIndex2, betAmount2)
+      .placeBet(outcomeIndex2, betAmount2, [])
       .accounts({
+        ...solBetAccounts,
         market: marketPDA,
         bettor: bettor2.publicKey,
         bet: betPDA2,
@@ -123,6 +183,12 @@ describe("prediction-market", () => {
     const marketAccount = await program.account.market.fetch(marketPDA);
     assert.isTrue(marketAccount.totalBets[outcomeIndex1].eq(betAmount1));
     assert.isTrue(marketAccount.totalBets[outcomeIndex2].eq(betAmount2));
+
+    const stats = await program.methods.getMarketStats().accounts({ market: marketPDA }).view();
+    assert.deepEqual(stats.impliedProbabilitiesBps, [3_333, 6_667]);
+    assert.isTrue(stats.poolSize.eq(betAmount1.add(betAmount2)));
+    assert.equal(stats.bettorCount, 2);
+    assert.isTrue(stats.timeLeft.gtn(0), "Betting should still be open");
   });
 
   it("Resolving the market", async () => {
@@ -136,6 +202,7 @@ describe("prediction-market", () => {
     await program.methods
       .resolveMarket(winningOutcome)
       .accounts({
+        ...standalone,
         market: marketPDA,
         authority: authority.publicKey
       })
@@ -167,6 +234,7 @@ describe("prediction-market", () => {
       await program.methods
         .claimPayout()
         .accounts({
+          ...solBetAccounts,
           market: marketPDA,
           bet: betPDA1,
           bettor: bettor1.publicKey,
@@ -181,6 +249,7 @@ describe("prediction-market", () => {
       await program.methods
         .claimPayout()
         .accounts({
+          ...solBetAccounts,
           market: marketPDA,
           bet: betPDA1,
           bettor: bettor1.publicKey,
@@ -231,6 +300,1242 @@ describe("prediction-market", () => {
     console.log(`Expected: ${expectedPayout} lamports (${Number(expectedPayout) / LAMPORTS_PER_SOL} SOL)`);
   });
 
+  it("Oracle markets cannot be resolved by the authority", async () => {
+    const question = "Will SOL be above $200?";
+    const [marketPDA] = await getMarketPDA(question);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 2);
+    const oracle = {
+      feed: anchor.web3.Keypair.generate().publicKey,
+      comparison: { greaterThan: {} },
+      threshold: new BN(200_00000000),
+      expo: -8,
+      maxStaleness: new BN(60),
+      maxConfidenceBps: 100,
+    };
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), oracle, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({
+        ...solCollateral, ...factoryAccounts, ...standalone,
+        market: marketPDA,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.equal(marketAccount.oracle.feed.toBase58(), oracle.feed.toBase58());
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    try {
+      await program.methods
+        .resolveMarket(0)
+        .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      assert.fail("Authority should not resolve an oracle market");
+    } catch (error) {
+      assert.include(error.message, "OracleMarket");
+    }
+  });
+
+  it("Unchallenged proposals finalize after the challenge window", async () => {
+    const question = "Will the launch ship on time?";
+    const [marketPDA] = await getMarketPDA(question);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 2);
+    const bond = new BN(0.05 * LAMPORTS_PER_SOL);
+    const optimistic = { arbiter: authority.publicKey, bond, challengePeriod: new BN(3), arbitrationPeriod: new BN(3600) };
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, optimistic, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await program.methods
+      .proposeOutcome(1)
+      .accounts({ ...standalone, market: marketPDA, proposer: bettor1.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor1])
+      .rpc();
+
+    try {
+      await program.methods.finalizeOutcome().accounts({ market: marketPDA, proposer: bettor1.publicKey }).rpc();
+      assert.fail("Should not finalize during the challenge window");
+    } catch (error) {
+      assert.include(error.message, "ChallengeWindowOpen");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 4000));
+    const proposerBefore = await provider.connection.getBalance(bettor1.publicKey);
+    await program.methods.finalizeOutcome().accounts({ market: marketPDA, proposer: bettor1.publicKey }).rpc();
+    const proposerAfter = await provider.connection.getBalance(bettor1.publicKey);
+
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.isTrue(marketAccount.resolved);
+    assert.equal(marketAccount.winningOutcome, 1);
+    assert.equal(proposerAfter - proposerBefore, bond.toNumber(), "Proposer bond should be returned");
+    assert.isNull(marketAccount.proposedOutcome, "The proposal is cleared once settled");
+
+    try {
+      await program.methods.finalizeOutcome().accounts({ market: marketPDA, proposer: bettor1.publicKey }).rpc();
+      assert.fail("A finalized proposal should not pay its bond out again");
+    } catch (error) {
+      assert.include(error.message, "MarketNotActive");
+    }
+    assert.equal(await provider.connection.getBalance(bettor1.publicKey), proposerAfter);
+  });
+
+  it("Disputed proposals are settled by the arbiter", async () => {
+    const question = "Will the vote pass?";
+    const [marketPDA] = await getMarketPDA(question);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 2);
+    const bond = new BN(0.05 * LAMPORTS_PER_SOL);
+    const optimistic = { arbiter: authority.publicKey, bond, challengePeriod: new BN(60), arbitrationPeriod: new BN(3600) };
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, optimistic, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await program.methods
+      .proposeOutcome(0)
+      .accounts({ ...standalone, market: marketPDA, proposer: bettor1.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor1])
+      .rpc();
+    await program.methods
+      .disputeOutcome()
+      .accounts({ market: marketPDA, disputer: bettor2.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor2])
+      .rpc();
+
+    const disputerBefore = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods
+      .arbitrate(1)
+      .accounts({
+        market: marketPDA,
+        arbiter: authority.publicKey,
+        proposer: bettor1.publicKey,
+        disputer: bettor2.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+    const disputerAfter = await provider.connection.getBalance(bettor2.publicKey);
+
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.equal(marketAccount.winningOutcome, 1);
+    assert.equal(disputerAfter - disputerBefore, bond.muln(2).toNumber(), "Disputer should win both bonds");
+  });
+
+  it("Disputes the arbiter never rules on are refunded with both bonds", async () => {
+    const question = "Will the arbiter show up?";
+    const [marketPDA] = await getMarketPDA(question);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 2);
+    const bond = new BN(0.05 * LAMPORTS_PER_SOL);
+    const optimistic = { arbiter: authority.publicKey, bond, challengePeriod: new BN(2), arbitrationPeriod: new BN(2) };
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, optimistic, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await program.methods
+      .proposeOutcome(0)
+      .accounts({ ...standalone, market: marketPDA, proposer: bettor1.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor1])
+      .rpc();
+    await program.methods
+      .disputeOutcome()
+      .accounts({ market: marketPDA, disputer: bettor2.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor2])
+      .rpc();
+
+    const expireAccounts = { market: marketPDA, proposer: bettor1.publicKey, disputer: bettor2.publicKey };
+    try {
+      await program.methods.expireDispute().accounts(expireAccounts).rpc();
+      assert.fail("The arbiter still has time to rule");
+    } catch (error) {
+      assert.include(error.message, "ArbitrationWindowOpen");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    try {
+      await program.methods
+        .arbitrate(0)
+        .accounts({ ...expireAccounts, arbiter: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      assert.fail("The arbiter is too late to rule");
+    } catch (error) {
+      assert.include(error.message, "ArbitrationClosed");
+    }
+
+    const proposerBefore = await provider.connection.getBalance(bettor1.publicKey);
+    const disputerBefore = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods.expireDispute().accounts(expireAccounts).rpc();
+
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.isTrue(marketAccount.refundable, "The market falls back to refunds");
+    assert.equal(await provider.connection.getBalance(bettor1.publicKey) - proposerBefore, bond.toNumber());
+    assert.equal(await provider.connection.getBalance(bettor2.publicKey) - disputerBefore, bond.toNumber());
+  });
+
+  it("Trading and redeeming LMSR shares", async () => {
+    const question = "Will the LMSR market price rain?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [positionPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("position"), marketPDA.toBuffer(), bettor1.publicKey.toBuffer()],
+      program.programId
+    );
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 6);
+    const b = new BN(0.5 * LAMPORTS_PER_SOL);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, b, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const shares = new BN(0.2 * LAMPORTS_PER_SOL);
+    await program.methods
+      .buyShares(0, shares, new BN(0.2 * LAMPORTS_PER_SOL))
+      .accounts({ market: marketPDA, position: positionPDA, trader: bettor1.publicKey, systemProgram: SystemProgram.programId })
+      .signers([bettor1])
+      .rpc();
+
+    let marketAccount = await program.account.market.fetch(marketPDA);
+    assert.isTrue(marketAccount.lmsr.shares[0].eq(shares));
+
+    // selling half back leaves the other half to redeem
+    await program.methods
+      .sellShares(0, shares.divn(2), new BN(0))
+      .accounts({ market: marketPDA, position: positionPDA, trader: bettor1.publicKey })
+      .signers([bettor1])
+      .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 7000));
+    await program.methods
+      .resolveMarket(0)
+      .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const before = await provider.connection.getBalance(bettor1.publicKey);
+    await program.methods
+      .redeemShares()
+      .accounts({ market: marketPDA, position: positionPDA, trader: bettor1.publicKey })
+      .signers([bettor1])
+      .rpc();
+    const after = await provider.connection.getBalance(bettor1.publicKey);
+    assert.equal(after - before, shares.divn(2).toNumber(), "Each winning share redeems for one lamport");
+  });
+
+  it("Splitting, merging and redeeming outcome tokens", async () => {
+    const question = "Will outcome tokens trade?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [mint0] = PublicKey.findProgramAddressSync(
+      [Buffer.from("outcome_mint"), marketPDA.toBuffer(), Buffer.from([0])],
+      program.programId
+    );
+    const [mint1] = PublicKey.findProgramAddressSync(
+      [Buffer.from("outcome_mint"), marketPDA.toBuffer(), Buffer.from([1])],
+      program.programId
+    );
+    const userToken0 = getAssociatedTokenAddressSync(mint0, bettor2.publicKey);
+    const userToken1 = getAssociatedTokenAddressSync(mint1, bettor2.publicKey);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 6);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .initOutcomeMints()
+      .accounts({
+        market: marketPDA,
+        authority: authority.publicKey,
+        outcomeMint0: mint0,
+        outcomeMint1: mint1,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const splitMergeAccounts = {
+      market: marketPDA,
+      user: bettor2.publicKey,
+      outcomeMint0: mint0,
+      outcomeMint1: mint1,
+      userToken0,
+      userToken1,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+    const amount = new BN(0.1 * LAMPORTS_PER_SOL);
+    await program.methods.split(amount).accounts(splitMergeAccounts).signers([bettor2]).rpc();
+    await program.methods.merge(amount.divn(2)).accounts(splitMergeAccounts).signers([bettor2]).rpc();
+
+    const balance0 = await provider.connection.getTokenAccountBalance(userToken0);
+    const balance1 = await provider.connection.getTokenAccountBalance(userToken1);
+    assert.equal(balance0.value.amount, amount.divn(2).toString());
+    assert.equal(balance1.value.amount, amount.divn(2).toString());
+
+    await new Promise((resolve) => setTimeout(resolve, 7000));
+    await program.methods
+      .resolveMarket(1)
+      .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const before = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods
+      .redeem(amount.divn(2))
+      .accounts({
+        market: marketPDA,
+        user: bettor2.publicKey,
+        winningMint: mint1,
+        userToken: userToken1,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bettor2])
+      .rpc();
+    const after = await provider.connection.getBalance(bettor2.publicKey);
+    assert.equal(after - before, amount.divn(2).toNumber());
+  });
+
+  it("Betting and claiming with SPL token collateral", async () => {
+    const question = "Will USDC markets settle in USDC?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [vaultPDA] = PublicKey.findProgramAddressSync([Buffer.from("vault"), marketPDA.toBuffer()], program.programId);
+    const [betPDA] = await getBetPDA(marketPDA, bettor1.publicKey);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 6);
+
+    const usdc = await createMint(provider.connection, authority, authority.publicKey, null, 6);
+    const bettorToken = await createAssociatedTokenAccount(provider.connection, bettor1, usdc, bettor1.publicKey);
+    await mintTo(provider.connection, authority, usdc, bettorToken, authority, 100_000_000);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({
+        ...factoryAccounts, ...standalone,
+        market: marketPDA,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+        collateralMint: usdc,
+        vault: vaultPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([authority])
+      .rpc();
+
+    const tokenBetAccounts = { vault: vaultPDA, bettorToken, tokenProgram: TOKEN_PROGRAM_ID };
+    const betAmount = new BN(25_000_000);
+    await program.methods
+      .placeBet(0, betAmount, [])
+      .accounts({
+        ...tokenBetAccounts,
+        market: marketPDA,
+        bettor: bettor1.publicKey,
+        bet: betPDA,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority, bettor1])
+      .rpc();
+
+    let vaultBalance = await provider.connection.getTokenAccountBalance(vaultPDA);
+    assert.equal(vaultBalance.value.amount, betAmount.toString());
+
+    await new Promise((resolve) => setTimeout(resolve, 7000));
+    await program.methods
+      .resolveMarket(0)
+      .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .claimPayout()
+      .accounts({ ...tokenBetAccounts, market: marketPDA, bet: betPDA, bettor: bettor1.publicKey, authority: authority.publicKey })
+      .signers([authority, bettor1])
+      .rpc();
+
+    vaultBalance = await provider.connection.getTokenAccountBalance(vaultPDA);
+    const bettorBalance = await provider.connection.getTokenAccountBalance(bettorToken);
+    assert.equal(vaultBalance.value.amount, "0");
+    assert.equal(bettorBalance.value.amount, "100000000");
+  });
+
+  it("Unresolved markets become refundable after the resolution deadline", async () => {
+    const question = "Will anyone resolve this?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [betPDA] = await getBetPDA(marketPDA, bettor2.publicKey);
+    const now = Math.floor(Date.now() / 1000);
+    const bettingCloseTime = new BN(now + 2);
+    const resolutionDeadline = new BN(now + 4);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], bettingCloseTime, bettingCloseTime, resolutionDeadline, null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const betAmount = new BN(0.1 * LAMPORTS_PER_SOL);
+    await program.methods
+      .placeBet(1, betAmount, [])
+      .accounts({
+        ...solBetAccounts,
+        market: marketPDA,
+        bettor: bettor2.publicKey,
+        bet: betPDA,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority, bettor2])
+      .rpc();
+
+    try {
+      await program.methods.markRefundable().accounts({ ...standalone, market: marketPDA }).rpc();
+      assert.fail("Should not be refundable before the deadline");
+    } catch (error) {
+      assert.include(error.message, "ResolutionDeadlineNotPassed");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await program.methods.markRefundable().accounts({ ...standalone, market: marketPDA }).rpc();
+
+    const before = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods
+      .claimRefund()
+      .accounts({ ...solBetAccounts, market: marketPDA, bet: betPDA, bettor: bettor2.publicKey })
+      .signers([bettor2])
+      .rpc();
+    const after = await provider.connection.getBalance(bettor2.publicKey);
+
+    // stake plus the bet account's rent comes back
+    assert.isAtLeast(after - before, betAmount.toNumber());
+    assert.isNull(await provider.connection.getAccountInfo(betPDA));
+  });
+
+  it("Updating market metadata before the first bet", async () => {
+    const question = "Will the front-end show this category?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [betPDA] = await getBetPDA(marketPDA, bettor1.publicKey);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 60);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const metadata = {
+      category: { other: {} },
+      tags: ["sol", "defi"],
+      resolutionSource: "Pyth SOL/USD",
+      uri: "https://example.com/markets/sol.json",
+      contentHash: Array(32).fill(7),
+    };
+    await program.methods
+      .updateMarketMetadata(metadata)
+      .accounts({ market: marketPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.equal(marketAccount.metadata.resolutionSource, metadata.resolutionSource);
+    assert.deepEqual(marketAccount.metadata.tags, metadata.tags);
+    assert.equal(marketAccount.metadata.uri, metadata.uri);
+
+    await program.methods
+      .placeBet(0, new BN(0.01 * LAMPORTS_PER_SOL), [])
+      .accounts({
+        ...solBetAccounts,
+        market: marketPDA,
+        bettor: bettor1.publicKey,
+        bet: betPDA,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority, bettor1])
+      .rpc();
+
+    try {
+      await program.methods
+        .updateMarketMetadata(defaultMetadata)
+        .accounts({ market: marketPDA, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      assert.fail("Metadata should be frozen after the first bet");
+    } catch (error) {
+      assert.include(error.message, "MarketHasBets");
+    }
+  });
+
+  it("Factory counts markets, collects fees and enforces the allowlist", async () => {
+    const factoryBefore = await program.account.marketFactory.fetch(factoryPDA);
+    const index = await program.account.categoryIndex.fetch(otherIndexPDA);
+    assert.equal(index.marketIds.length, factoryBefore.marketCount.toNumber(), "Every market so far is in the Other index");
+    assert.isAtLeast(
+      await provider.connection.getBalance(treasury.publicKey),
+      creationFee.muln(factoryBefore.marketCount.toNumber()).toNumber(),
+      "Treasury should hold the creation fees"
+    );
+
+    try {
+      await program.methods
+        .updateFactory(creationFee, true, new BN(0))
+        .accounts({ factory: factoryPDA, admin: authority.publicKey, treasury: treasury.publicKey })
+        .signers([authority])
+        .rpc();
+      assert.fail("Winners must get a claim window");
+    } catch (error) {
+      assert.include(error.message, "InvalidClaimWindow");
+    }
+    await program.methods
+      .updateFactory(creationFee, true, claimWindow)
+      .accounts({ factory: factoryPDA, admin: authority.publicKey, treasury: treasury.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const question = "Can only allowed creators list markets?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [allowancePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("creator"), authority.publicKey.toBuffer()],
+      program.programId
+    );
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 60);
+    const create = (creatorAllowance: PublicKey | null) =>
+      program.methods
+        .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+        .accounts({
+          ...solCollateral,
+          ...factoryAccounts, ...standalone,
+          creatorAllowance,
+          market: marketPDA,
+          authority: authority.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+    try {
+      await create(null);
+      assert.fail("Creator is not on the allowlist yet");
+    } catch (error) {
+      assert.include(error.message, "CreatorNotAllowed");
+    }
+
+    await program.methods
+      .setCreatorAllowed(true)
+      .accounts({
+        factory: factoryPDA,
+        admin: authority.publicKey,
+        creator: authority.publicKey,
+        creatorAllowance: allowancePDA,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+    await create(allowancePDA);
+
+    const factoryAfter = await program.account.marketFactory.fetch(factoryPDA);
+    assert.isTrue(factoryAfter.marketCount.eq(factoryBefore.marketCount.addn(1)));
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.isTrue(marketAccount.id.eq(factoryBefore.marketCount));
+
+    await program.methods
+      .updateFactory(creationFee, false, claimWindow)
+      .accounts({ factory: factoryPDA, admin: authority.publicKey, treasury: treasury.publicKey })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Conditional markets are refunded when the parent goes the other way", async () => {
+    const parentQuestion = "Will team A win the semi?";
+    const childQuestion = "If team A wins the semi, will they win the final?";
+    const now = Math.floor(Date.now() / 1000);
+    const parentTime = new BN(now + 3);
+    const childTime = new BN(now + 60);
+
+    const [parentPDA] = await getMarketPDA(parentQuestion);
+    await program.methods
+      .createMarket(parentQuestion, ["Yes", "No"], parentTime, parentTime, parentTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: parentPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const [childPDA] = await getMarketPDA(childQuestion);
+    await program.methods
+      .createMarket(childQuestion, ["Yes", "No"], childTime, childTime, childTime.addn(3600), null, null, null, defaultMetadata, 0, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, parentMarket: parentPDA, market: childPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const childAccount = await program.account.market.fetch(childPDA);
+    assert.equal(childAccount.parentMarket.toBase58(), parentPDA.toBase58());
+    assert.equal(childAccount.parentOutcome, 0);
+
+    await new Promise((resolve) => setTimeout(resolve, 4000));
+    await program.methods
+      .resolveMarket(1) // team A lost the semi
+      .accounts({ ...standalone, market: parentPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods.markRefundable().accounts({ market: childPDA, parentMarket: parentPDA }).rpc();
+    const refundable = await program.account.market.fetch(childPDA);
+    assert.isTrue(refundable.refundable, "Child market should be refundable");
+  });
+
+  it("Scalar markets pay LONG and SHORT by where the value lands", async () => {
+    const question = "What will the CPI print be (bps)?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [longBet] = await getBetPDA(marketPDA, bettor1.publicKey);
+    const [shortBet] = await getBetPDA(marketPDA, bettor2.publicKey);
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 5);
+    const kind = { scalar: { min: new BN(200), max: new BN(400) } };
+
+    await program.methods
+      .createMarket(question, ["Long", "Short"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, kind, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    // outcome tokens redeem by a winning outcome, which scalar markets never have
+    const [mint0, mint1] = [0, 1].map(
+      (outcome) =>
+        PublicKey.findProgramAddressSync([Buffer.from("outcome_mint"), marketPDA.toBuffer(), Buffer.from([outcome])], program.programId)[0]
+    );
+    try {
+      await program.methods
+        .initOutcomeMints()
+        .accounts({ market: marketPDA, authority: authority.publicKey, outcomeMint0: mint0, outcomeMint1: mint1, tokenProgram: TOKEN_PROGRAM_ID, systemProgram: SystemProgram.programId })
+        .signers([authority])
+        .rpc();
+      assert.fail("Scalar markets should not get outcome tokens");
+    } catch (err) {
+      assert.include(err.message, "ScalarMarket");
+    }
+
+    const stake = new BN(0.1 * LAMPORTS_PER_SOL);
+    for (const [bettor, bet, side] of [[bettor1, longBet, 0], [bettor2, shortBet, 1]] as const) {
+      await program.methods
+        .placeBet(side, stake, [])
+        .accounts({
+          ...solBetAccounts,
+          market: marketPDA,
+          bettor: bettor.publicKey,
+          bet,
+          authority: authority.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([authority, bettor])
+        .rpc();
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 6000));
+    await program.methods
+      .resolveScalar(new BN(350)) // 75% of the way from min to max
+      .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const before = await provider.connection.getBalance(marketPDA);
+    await program.methods
+      .claimPayout()
+      .accounts({ ...solBetAccounts, market: marketPDA, bet: longBet, bettor: bettor1.publicKey, authority: authority.publicKey })
+      .signers([authority, bettor1])
+      .rpc();
+    const after = await provider.connection.getBalance(marketPDA);
+    assert.equal(before - after, stake.muln(2).muln(3).divn(4).toNumber(), "LONG should take 75% of the pool");
+  });
+
+  it("Matching bids and asks on the order book", async () => {
+    const question = "Will the order book cross?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [mint0, mint1] = [0, 1].map(
+      (outcome) =>
+        PublicKey.findProgramAddressSync([Buffer.from("outcome_mint"), marketPDA.toBuffer(), Buffer.from([outcome])], program.programId)[0]
+    );
+    const [bookPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("book"), marketPDA.toBuffer(), Buffer.from([0])],
+      program.programId
+    );
+    const [escrowPDA] = PublicKey.findProgramAddressSync([Buffer.from("book_escrow"), bookPDA.toBuffer()], program.programId);
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 3600);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .initOutcomeMints()
+      .accounts({ market: marketPDA, authority: authority.publicKey, outcomeMint0: mint0, outcomeMint1: mint1, tokenProgram: TOKEN_PROGRAM_ID, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .initOrderBook(0)
+      .accounts({ market: marketPDA, orderBook: bookPDA, outcomeMint: mint0, escrow: escrowPDA, payer: authority.publicKey, tokenProgram: TOKEN_PROGRAM_ID, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    // bettor2 mints a complete set and offers the Yes token, bettor1 bids above the ask
+    const quantity = new BN(20_000_000);
+    await program.methods
+      .split(quantity)
+      .accounts({
+        market: marketPDA,
+        user: bettor2.publicKey,
+        outcomeMint0: mint0,
+        outcomeMint1: mint1,
+        userToken0: getAssociatedTokenAddressSync(mint0, bettor2.publicKey),
+        userToken1: getAssociatedTokenAddressSync(mint1, bettor2.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([bettor2])
+      .rpc();
+    const placeOrder = (owner: anchor.web3.Keypair, side: object, price: number, amount: BN) =>
+      program.methods
+        .placeOrder(side as any, new BN(price), amount)
+        .accounts({
+          market: marketPDA,
+          orderBook: bookPDA,
+          outcomeMint: mint0,
+          escrow: escrowPDA,
+          ownerToken: getAssociatedTokenAddressSync(mint0, owner.publicKey),
+          owner: owner.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    const matchOrders = (buyer: anchor.web3.Keypair, seller: anchor.web3.Keypair) =>
+      program.methods
+        .matchOrders(10)
+        .accounts({ market: marketPDA, orderBook: bookPDA, escrow: escrowPDA, tokenProgram: TOKEN_PROGRAM_ID })
+        .remainingAccounts([
+          { pubkey: getAssociatedTokenAddressSync(mint0, buyer.publicKey), isWritable: true, isSigner: false },
+          { pubkey: buyer.publicKey, isWritable: true, isSigner: false },
+          { pubkey: seller.publicKey, isWritable: true, isSigner: false },
+        ])
+        .rpc();
+    await placeOrder(bettor2, { ask: {} }, 6_000, quantity);
+    await placeOrder(bettor1, { bid: {} }, 6_500, quantity);
+
+    const buyerToken = getAssociatedTokenAddressSync(mint0, bettor1.publicKey);
+    const sellerBefore = await provider.connection.getBalance(bettor2.publicKey);
+    await matchOrders(bettor1, bettor2);
+    const sellerAfter = await provider.connection.getBalance(bettor2.publicKey);
+
+    // the resting ask was first, so the fill happens at its price
+    assert.equal(sellerAfter - sellerBefore, 12_000_000, "Seller is paid 0.6 lamports per token");
+    const bought = await provider.connection.getTokenAccountBalance(buyerToken);
+    assert.equal(bought.value.amount, quantity.toString());
+    const escrow = await provider.connection.getTokenAccountBalance(escrowPDA);
+    assert.equal(escrow.value.amount, "0");
+    let book = await program.account.orderBook.fetch(bookPDA);
+    assert.equal(book.bidCount, 0);
+    assert.equal(book.askCount, 0);
+
+    // dust orders can't fill up the book
+    try {
+      await placeOrder(bettor1, { bid: {} }, 6_500, new BN(10_000));
+      assert.fail("Orders below the minimum size should be rejected");
+    } catch (err) {
+      assert.include(err.message, "OrderTooSmall");
+    }
+
+    // a bid whose token account was closed is cancelled and refunded by the crank
+    await placeOrder(bettor2, { bid: {} }, 6_500, quantity);
+    await closeAccount(provider.connection, bettor2, getAssociatedTokenAddressSync(mint0, bettor2.publicKey), bettor2.publicKey, bettor2);
+    await placeOrder(bettor1, { ask: {} }, 6_000, quantity);
+    const ghostBefore = await provider.connection.getBalance(bettor2.publicKey);
+    await matchOrders(bettor2, bettor1);
+    assert.equal((await provider.connection.getBalance(bettor2.publicKey)) - ghostBefore, 13_000_000, "The bid's lamports go back");
+    book = await program.account.orderBook.fetch(bookPDA);
+    assert.equal(book.bidCount, 0);
+    assert.equal(book.askCount, 1, "The ask keeps resting");
+
+    // cancelling hands back whatever the order locked: tokens for asks, lamports for bids
+    const cancel = (side: object, seq: BN) =>
+      program.methods
+        .cancelOrder(side as any, seq)
+        .accounts({ market: marketPDA, orderBook: bookPDA, escrow: escrowPDA, ownerToken: buyerToken, owner: bettor1.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+        .signers([bettor1])
+        .rpc();
+    await cancel({ ask: {} }, book.asks[0].seq);
+    assert.equal((await provider.connection.getTokenAccountBalance(buyerToken)).value.amount, quantity.toString());
+
+    const bidSeq = (await program.account.orderBook.fetch(bookPDA)).nextSeq;
+    const bidderBefore = await provider.connection.getBalance(bettor1.publicKey);
+    await placeOrder(bettor1, { bid: {} }, 5_000, quantity);
+    await cancel({ bid: {} }, bidSeq);
+    assert.equal(await provider.connection.getBalance(bettor1.publicKey), bidderBefore, "The locked lamports come back");
+    try {
+      await cancel({ bid: {} }, bidSeq);
+      assert.fail("A cancelled order can't be cancelled again");
+    } catch (err) {
+      assert.include(err.message, "OrderNotFound");
+    }
+    book = await program.account.orderBook.fetch(bookPDA);
+    assert.equal(book.bidCount, 0);
+    assert.equal(book.askCount, 0);
+  });
+
+  it("Refunding outcome tokens when a market becomes refundable", async () => {
+    const question = "Will anyone resolve the token market?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [mint0, mint1] = [0, 1].map(
+      (outcome) =>
+        PublicKey.findProgramAddressSync([Buffer.from("outcome_mint"), marketPDA.toBuffer(), Buffer.from([outcome])], program.programId)[0]
+    );
+    const userToken0 = getAssociatedTokenAddressSync(mint0, bettor2.publicKey);
+    const userToken1 = getAssociatedTokenAddressSync(mint1, bettor2.publicKey);
+    const now = Math.floor(Date.now() / 1000);
+    const bettingCloseTime = new BN(now + 2);
+    const resolutionDeadline = new BN(now + 4);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], bettingCloseTime, bettingCloseTime, resolutionDeadline, null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .initOutcomeMints()
+      .accounts({ market: marketPDA, authority: authority.publicKey, outcomeMint0: mint0, outcomeMint1: mint1, tokenProgram: TOKEN_PROGRAM_ID, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const splitMergeAccounts = {
+      market: marketPDA,
+      user: bettor2.publicKey,
+      outcomeMint0: mint0,
+      outcomeMint1: mint1,
+      userToken0,
+      userToken1,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+    const amount = new BN(100_000);
+    await program.methods.split(amount).accounts(splitMergeAccounts).signers([bettor2]).rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await program.methods.markRefundable().accounts({ ...standalone, market: marketPDA }).rpc();
+
+    // full sets merge back at par, single tokens refund an equal share of their set
+    const before = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods.merge(amount.divn(2)).accounts(splitMergeAccounts).signers([bettor2]).rpc();
+    await program.methods
+      .refundTokens(amount.divn(2))
+      .accounts({ market: marketPDA, user: bettor2.publicKey, outcomeMint: mint0, userToken: userToken0, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bettor2])
+      .rpc();
+    const after = await provider.connection.getBalance(bettor2.publicKey);
+    assert.equal(after - before, amount.divn(2).toNumber() + amount.divn(4).toNumber());
+
+    const balance0 = await provider.connection.getTokenAccountBalance(userToken0);
+    assert.equal(balance0.value.amount, "0");
+  });
+
+  it("Bet limits reject bets outside the market's bounds", async () => {
+    const question = "Will the whale be stopped?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [betPDA] = await getBetPDA(marketPDA, bettor1.publicKey);
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 3600);
+    const limits = { ...noLimits, minBet: new BN(0.01 * LAMPORTS_PER_SOL), maxBetPerBettor: new BN(0.05 * LAMPORTS_PER_SOL) };
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(3600), null, null, null, defaultMetadata, null, binary, limits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const betAccounts = { ...solBetAccounts, market: marketPDA, bettor: bettor1.publicKey, bet: betPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId };
+    for (const [amount, error] of [[0.005, "BetBelowMinimum"], [0.06, "BetAboveMaximum"]] as const) {
+      try {
+        await program.methods.placeBet(0, new BN(amount * LAMPORTS_PER_SOL), []).accounts(betAccounts).signers([authority, bettor1]).rpc();
+        assert.fail(`A bet of ${amount} SOL should be rejected`);
+      } catch (err) {
+        assert.include(err.message, error);
+      }
+    }
+
+    await program.methods.placeBet(0, new BN(0.05 * LAMPORTS_PER_SOL), []).accounts(betAccounts).signers([authority, bettor1]).rpc();
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    assert.isTrue(marketAccount.totalBets[0].eq(limits.maxBetPerBettor));
+  });
+
+  it("Withdrawing a bet leaves the exit fee in the pool", async () => {
+    const question = "Will the bettor change their mind?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [betPDA] = await getBetPDA(marketPDA, bettor1.publicKey);
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 3600);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const stake = new BN(0.1 * LAMPORTS_PER_SOL);
+    await program.methods
+      .placeBet(0, stake, [])
+      .accounts({ ...solBetAccounts, market: marketPDA, bettor: bettor1.publicKey, bet: betPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority, bettor1])
+      .rpc();
+
+    const marketBefore = await provider.connection.getBalance(marketPDA);
+    await program.methods
+      .withdrawBet()
+      .accounts({ ...solBetAccounts, market: marketPDA, bet: betPDA, bettor: bettor1.publicKey })
+      .signers([bettor1])
+      .rpc();
+    const marketAfter = await provider.connection.getBalance(marketPDA);
+
+    // right after creation the fee is close to the 1% floor and never above the 10% cap
+    const marketAccount = await program.account.market.fetch(marketPDA);
+    const fee = marketAccount.exitFees.toNumber();
+    assert.isAtLeast(fee, stake.toNumber() / 100);
+    assert.isAtMost(fee, stake.toNumber() / 10);
+    assert.equal(marketBefore - marketAfter, stake.toNumber() - fee, "The fee stays in the market");
+    assert.isTrue(marketAccount.totalBets[0].isZero());
+    assert.equal(marketAccount.bettorCount, 0);
+    assert.isNull(await provider.connection.getAccountInfo(betPDA));
+  });
+
+  it("Committee markets resolve at the vote threshold and deadlock into refunds", async () => {
+    const members = [authority, bettor1, bettor2];
+    const eventTime = new BN(Math.floor(Date.now() / 1000) + 4);
+    const createCommitteeMarket = async (question: string, threshold: number) => {
+      const [marketPDA] = await getMarketPDA(question);
+      const committee = { members: members.map((member) => member.publicKey), threshold };
+      await program.methods
+        .createMarket(question, ["Yes", "No"], eventTime, eventTime, eventTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, committee)
+        .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+        .signers([authority])
+        .rpc();
+      return marketPDA;
+    };
+    const vote = (market: PublicKey, member: anchor.web3.Keypair, outcome: number) =>
+      program.methods.voteResolution(outcome).accounts({ ...standalone, market, member: member.publicKey }).signers([member]).rpc();
+
+    // a minority threshold would let one side of a split committee win
+    try {
+      await createCommitteeMarket("Will one of three members be enough?", 1);
+      assert.fail("Thresholds must be a majority of the committee");
+    } catch (err) {
+      assert.include(err.message, "InvalidCommittee");
+    }
+    const majorityPDA = await createCommitteeMarket("Will two of three members agree?", 2);
+    const unanimousPDA = await createCommitteeMarket("Will all three members agree?", 3);
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+
+    await vote(majorityPDA, authority, 1);
+    await vote(majorityPDA, bettor1, 0);
+    for (const [voter, error] of [[authority, "AlreadyVoted"], [treasury, "NotCommitteeMember"]] as const) {
+      try {
+        await vote(majorityPDA, voter, 1);
+        assert.fail("The vote should be rejected");
+      } catch (err) {
+        assert.include(err.message, error);
+      }
+    }
+    let market = await program.account.market.fetch(majorityPDA);
+    assert.isTrue(market.isActive, "One vote each is below the threshold");
+
+    await vote(majorityPDA, bettor2, 1);
+    market = await program.account.market.fetch(majorityPDA);
+    assert.isTrue(market.resolved);
+    assert.equal(market.winningOutcome, 1);
+
+    // once the first two votes split, no outcome can reach three any more
+    await vote(unanimousPDA, authority, 0);
+    await vote(unanimousPDA, bettor1, 1);
+    market = await program.account.market.fetch(unanimousPDA);
+    assert.isFalse(market.resolved);
+    assert.isTrue(market.refundable, "A deadlocked committee falls back to refunds");
+  });
+
+  it("Liquidity providers get pro rata shares and withdraw their stake before close", async () => {
+    const question = "Will the pool stay liquid?";
+    const [marketPDA] = await getMarketPDA(question);
+    const getLpPDA = (owner: PublicKey) =>
+      PublicKey.findProgramAddressSync([Buffer.from("lp"), marketPDA.toBuffer(), owner.toBuffer()], program.programId)[0];
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 3600);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    // the first deposit mints one share per lamport of per-outcome stake, later ones pro rata
+    for (const [lp, amount] of [[bettor1, 0.2], [bettor2, 0.1]] as const) {
+      await program.methods
+        .addLiquidity(new BN(amount * LAMPORTS_PER_SOL))
+        .accounts({ market: marketPDA, lpPosition: getLpPDA(lp.publicKey), provider: lp.publicKey, systemProgram: SystemProgram.programId })
+        .signers([lp])
+        .rpc();
+    }
+    let market = await program.account.market.fetch(marketPDA);
+    assert.equal(market.lpStake.toNumber(), 0.15 * LAMPORTS_PER_SOL);
+    assert.equal(market.lpShares.toNumber(), 0.15 * LAMPORTS_PER_SOL);
+    market.totalBets.forEach((total) => assert.equal(total.toNumber(), 0.15 * LAMPORTS_PER_SOL));
+    const position = await program.account.lpPosition.fetch(getLpPDA(bettor2.publicKey));
+    assert.equal(position.shares.toNumber(), 0.05 * LAMPORTS_PER_SOL);
+
+    // half of bettor2's shares are worth half their stake on both outcomes
+    const shares = position.shares.divn(2);
+    const removeAccounts = { market: marketPDA, lpPosition: getLpPDA(bettor2.publicKey), provider: bettor2.publicKey };
+    const before = await provider.connection.getBalance(bettor2.publicKey);
+    await program.methods.removeLiquidity(shares).accounts(removeAccounts).signers([bettor2]).rpc();
+    const after = await provider.connection.getBalance(bettor2.publicKey);
+    assert.equal(after - before, shares.muln(2).toNumber());
+
+    market = await program.account.market.fetch(marketPDA);
+    assert.equal(market.lpStake.toNumber(), 0.125 * LAMPORTS_PER_SOL);
+    assert.equal(market.lpShares.toNumber(), 0.125 * LAMPORTS_PER_SOL);
+    market.totalBets.forEach((total) => assert.equal(total.toNumber(), 0.125 * LAMPORTS_PER_SOL));
+
+    try {
+      await program.methods.removeLiquidity(shares.addn(1)).accounts(removeAccounts).signers([bettor2]).rpc();
+      assert.fail("Removing more shares than held should fail");
+    } catch (err) {
+      assert.include(err.message, "InsufficientShares");
+    }
+  });
+
+  it("Claiming winning bets across several markets at once", async () => {
+    const resolutionTime = new BN(Math.floor(Date.now() / 1000) + 6);
+    const stake = new BN(0.05 * LAMPORTS_PER_SOL);
+    const markets: [PublicKey, PublicKey][] = [];
+    for (const question of ["Will batch claim one pay?", "Will batch claim two pay?", "Will batch claim three pay?"]) {
+      const [marketPDA] = await getMarketPDA(question);
+      await program.methods
+        .createMarket(question, ["Yes", "No"], resolutionTime, resolutionTime, resolutionTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+        .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+        .signers([authority])
+        .rpc();
+      for (const [bettor, outcome] of [[bettor1, 0], [bettor2, 1]] as const) {
+        const [betPDA] = await getBetPDA(marketPDA, bettor.publicKey);
+        await program.methods
+          .placeBet(outcome, stake, [])
+          .accounts({ ...solBetAccounts, market: marketPDA, bettor: bettor.publicKey, bet: betPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+          .signers([authority, bettor])
+          .rpc();
+      }
+      markets.push([marketPDA, (await getBetPDA(marketPDA, bettor1.publicKey))[0]]);
+    }
+
+    // bettor1 wins the first two markets and loses the third
+    await new Promise((resolve) => setTimeout(resolve, 7000));
+    for (const [[marketPDA], winner] of markets.map((pair, i) => [pair, i < 2 ? 0 : 1] as const)) {
+      await program.methods
+        .resolveMarket(winner)
+        .accounts({ ...standalone, market: marketPDA, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    }
+
+    const pairs = markets.flatMap(([market, bet]) => [
+      { pubkey: market, isWritable: true, isSigner: false },
+      { pubkey: bet, isWritable: true, isSigner: false },
+    ]);
+    const claimMany = () =>
+      program.methods.claimMany().accounts({ bettor: bettor1.publicKey }).remainingAccounts(pairs).signers([bettor1]).rpc();
+
+    const before = await provider.connection.getBalance(bettor1.publicKey);
+    await claimMany();
+    const afterFirst = await provider.connection.getBalance(bettor1.publicKey);
+    assert.isAbove(afterFirst - before, stake.muln(2).toNumber(), "Both winning bets pay more than their stake");
+    const bets = await Promise.all(markets.map(([, bet]) => program.account.bet.fetch(bet)));
+    assert.deepEqual(bets.map((bet) => bet.claimed), [true, true, false]);
+
+    // claimed and losing bets are skipped, so a second batch pays nothing
+    await claimMany();
+    assert.equal(await provider.connection.getBalance(bettor1.publicKey), afterFirst);
+
+    const [splMarket] = await getMarketPDA("Will USDC markets settle in USDC?");
+    const [splBet] = await getBetPDA(splMarket, bettor1.publicKey);
+    try {
+      await program.methods
+        .claimMany()
+        .accounts({ bettor: bettor1.publicKey })
+        .remainingAccounts([
+          { pubkey: splMarket, isWritable: true, isSigner: false },
+          { pubkey: splBet, isWritable: true, isSigner: false },
+        ])
+        .signers([bettor1])
+        .rpc();
+      assert.fail("SPL collateral markets should be rejected");
+    } catch (err) {
+      assert.include(err.message, "SolCollateralOnly");
+    }
+  });
+
+  it("Settled markets cannot be finalized during the claim window", async () => {
+    const question = "Will anyone claim their refund?";
+    const [marketPDA] = await getMarketPDA(question);
+    const [betPDA] = await getBetPDA(marketPDA, bettor2.publicKey);
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 2);
+
+    await program.methods
+      .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(2), null, null, null, defaultMetadata, null, binary, noLimits, null)
+      .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .placeBet(1, new BN(0.02 * LAMPORTS_PER_SOL), [])
+      .accounts({ ...solBetAccounts, market: marketPDA, bettor: bettor2.publicKey, bet: betPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority, bettor2])
+      .rpc();
+
+    // the sweep itself is covered by the resolution paths suite, which can warp past the window
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await program.methods.markRefundable().accounts({ ...standalone, market: marketPDA }).rpc();
+    try {
+      await program.methods
+        .finalizeMarket()
+        .accounts({ ...solCollateral, market: marketPDA, authority: authority.publicKey, factory: factoryPDA, treasury: treasury.publicKey, treasuryToken: null, authorityToken: null })
+        .rpc();
+      assert.fail("The claim window is still open");
+    } catch (err) {
+      assert.include(err.message, "ClaimPeriodOpen");
+    }
+  });
+
+  it("Parlays pay the product of every leg's odds once all legs win", async () => {
+    const questions = ["Will leg one come in?", "Will leg two come in?"];
+    const closeTime = new BN(Math.floor(Date.now() / 1000) + 15);
+    const markets: PublicKey[] = [];
+    for (const question of questions) {
+      const [marketPDA] = await getMarketPDA(question);
+      markets.push(marketPDA);
+      await program.methods
+        .createMarket(question, ["Yes", "No"], closeTime, closeTime, closeTime.addn(3600), null, null, null, defaultMetadata, null, binary, noLimits, null)
+        .accounts({ ...solCollateral, ...factoryAccounts, ...standalone, market: marketPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+        .signers([authority])
+        .rpc();
+      // even pools, so Yes pays 2x on each leg
+      for (const [bettor, outcome] of [[bettor1, 0], [bettor2, 1]] as const) {
+        const [betPDA] = await getBetPDA(marketPDA, bettor.publicKey);
+        await program.methods
+          .placeBet(outcome, new BN(0.01 * LAMPORTS_PER_SOL), [])
+          .accounts({ ...solBetAccounts, market: marketPDA, bettor: bettor.publicKey, bet: betPDA, authority: authority.publicKey, systemProgram: SystemProgram.programId })
+          .signers([authority, bettor])
+          .rpc();
+      }
+    }
+
+    const [housePDA] = PublicKey.findProgramAddressSync([Buffer.from("house")], program.programId);
+    await program.methods
+      .initParlayHouse()
+      .accounts({ house: housePDA, factory: factoryPDA, admin: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .fundParlayHouse(new BN(0.1 * LAMPORTS_PER_SOL))
+      .accounts({ house: housePDA, funder: authority.publicKey, systemProgram: SystemProgram.programId })
+      .signers([authority])
+      .rpc();
+
+    const [parlayPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("parlay"), bettor1.publicKey.toBuffer(), new BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const legs = markets.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }));
+
+    // an even seed on the first leg leaves its odds at 2x
+    const lpPDA = PublicKey.findProgramAddressSync([Buffer.from("lp"), markets[0].toBuffer(), authority.publicKey.toBuffer()], program.programId)[0];
+    const lpAccounts = { market: markets[0], lpPosition: lpPDA, provider: authority.publicKey, systemProgram: SystemProgram.programId };
+    await program.methods.addLiquidity(new BN(0.02 * LAMPORTS_PER_SOL)).accounts(lpAccounts).signers([authority]).rpc();
+
+    // a lamport's stake would be enough to lock every leg's withdrawals
+    try {
+      await program.methods
+        .placeParlay(Buffer.from([0, 0]), new BN(1))
+        .accounts({ parlay: parlayPDA, house: housePDA, bettor: bettor1.publicKey, systemProgram: SystemProgram.programId })
+        .remainingAccounts(legs)
+        .signers([bettor1])
+        .rpc();
+      assert.fail("Parlay stakes below the minimum should be rejected");
+    } catch (err) {
+      assert.include(err.message, "ParlayStakeTooSmall");
+    }
+
+    await program.methods
+      .placeParlay(Buffer.from([0, 0]), new BN(0.01 * LAMPORTS_PER_SOL))
+      .accounts({ parlay: parlayPDA, house: housePDA, bettor: bettor1.publicKey, systemProgram: SystemProgram.programId })
+      .remainingAccounts(legs)
+      .signers([bettor1])
+      .rpc();
+    const parlay = await program.account.parlay.fetch(parlayPDA);
+    assert.equal(parlay.potentialPayout.toNumber(), 0.038 * LAMPORTS_PER_SOL, "4x odds less the 5% house edge");
+
+    // the legs' pools are frozen for the parlay: nobody can pull a bet back out of them
+    try {
+      await program.methods
+        .withdrawBet()
+        .accounts({ ...solBetAccounts, market: markets[0], bet: (await getBetPDA(markets[0], bettor2.publicKey))[0], bettor: bettor2.publicKey })
+        .signers([bettor2])
+        .rpc();
+      assert.fail("Withdrawals should be locked while the parlay is open");
+    } catch (err) {
+      assert.include(err.message, "ParlayLegsOpen");
+    }
+    // and so is the LP seed that set the odds
+    const lpShares = (await program.account.lpPosition.fetch(lpPDA)).shares;
+    for (const lock of [
+      program.methods.removeLiquidity(lpShares).accounts({ market: markets[0], lpPosition: lpPDA, provider: authority.publicKey }),
+      program.methods.addLiquidity(new BN(0.02 * LAMPORTS_PER_SOL)).accounts(lpAccounts),
+    ]) {
+      try {
+        await lock.signers([authority]).rpc();
+        assert.fail("Liquidity should be locked while the parlay is open");
+      } catch (err) {
+        assert.include(err.message, "ParlayLegsOpen");
+      }
+    }
+
+    const settleAccounts = { parlay: parlayPDA, house: housePDA, bettor: bettor1.publicKey };
+    try {
+      await program.methods.settleParlay().accounts(settleAccounts).remainingAccounts(legs).rpc();
+      assert.fail("Parlay should not settle before its markets resolve");
+    } catch (err) {
+      assert.include(err.message, "ParlayNotSettled");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 16000));
+    for (const market of markets) {
+      await program.methods.resolveMarket(0).accounts({ ...standalone, market, authority: authority.publicKey }).signers([authority]).rpc();
+    }
+    const before = await provider.connection.getBalance(bettor1.publicKey);
+    const rent = await provider.connection.getBalance(parlayPDA);
+    await program.methods.settleParlay().accounts(settleAccounts).remainingAccounts(legs).rpc();
+    const after = await provider.connection.getBalance(bettor1.publicKey);
+
+    assert.equal(after - before - rent, 0.038 * LAMPORTS_PER_SOL, "Bettor is paid the fixed-odds payout");
+    const house = await program.account.parlayHouse.fetch(housePDA);
+    assert.isTrue(house.reserved.eqn(0), "The reservation is released");
+    for (const market of markets) {
+      const account = await program.account.market.fetch(market);
+      assert.equal(account.openParlayLegs, 0, "Settling unlocks the legs' markets");
+    }
+  });
+
   // Optional: Test that losing bettor cannot claim
   it("Losing bettor cannot claim payout", async () => {
     const question = "Will it rain today?";
@@ -241,6 +1546,7 @@ describe("prediction-market", () => {
       await program.methods
         .claimPayout()
         .accounts({
+          ...solBetAccounts,
           market: marketPDA,
           bet: betPDA2,
           bettor: bettor2.publicKey,
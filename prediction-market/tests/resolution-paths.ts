@@ -0,0 +1,356 @@
+import { Program } from "@coral-xyz/anchor";
+import { PredictionMarket } from "../target/types/prediction_market";
+import { Keypair, PublicKey, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { BN } from "bn.js";
+import { startAnchor, BankrunProvider } from "anchor-bankrun";
+import { Clock, ProgramTestContext } from "solana-bankrun";
+import { assert } from "chai";
+
+const IDL = require("../target/idl/prediction_market.json");
+
+// Every way a market can end, driven through the real instructions in an in-process bank.
+// The clock is warped instead of waited on, so challenge, arbitration and deadline windows can
+// be realistic. Each scenario pays every bet out and checks what is left in the market is the
+// rent plus rounding dust.
+describe("resolution paths", () => {
+  let context: ProgramTestContext;
+  let provider: BankrunProvider;
+  let program: Program<PredictionMarket>;
+
+  const authority = () => provider.wallet.publicKey;
+  const [alice, bob, carol, disputer] = [0, 1, 2, 3].map(() => Keypair.generate());
+
+  // alice and bob back outcome 0, carol backs outcome 1
+  const bets = [
+    [alice, 0, 1_000_000],
+    [bob, 0, 2_000_001],
+    [carol, 1, 4_999_999],
+  ] as const;
+  const pool = bets.reduce((sum, [, , amount]) => sum + amount, 0);
+
+  const defaultMetadata = { category: { other: {} }, tags: [], resolutionSource: "", uri: "", contentHash: Array(32).fill(0) };
+  const binary = { binary: {} };
+  const noLimits = { minBet: new BN(0), maxBetPerBettor: new BN(0), maxOutcomeShareBps: 0, allowlistRoot: null };
+  const solCollateral = { collateralMint: null, vault: null, tokenProgram: null };
+  const solBetAccounts = { vault: null, bettorToken: null, tokenProgram: null };
+  const standalone = { parentMarket: null };
+
+  const [factoryPDA] = PublicKey.findProgramAddressSync([Buffer.from("factory")], new PublicKey(IDL.address));
+  const [otherIndexPDA] = PublicKey.findProgramAddressSync([Buffer.from("category_index"), Buffer.from([6])], new PublicKey(IDL.address));
+  const treasury = Keypair.generate();
+  const claimWindow = 24 * 60 * 60;
+
+  const now = async () => Number((await context.banksClient.getClock()).unixTimestamp);
+
+  // moves to a new slot too, so repeating an identical transaction isn't deduplicated
+  const warpTo = async (unixTimestamp: number) => {
+    const clock = await context.banksClient.getClock();
+    const slot = clock.slot + 1n;
+    context.warpToSlot(slot);
+    context.setClock(new Clock(slot, clock.epochStartTimestamp, clock.epoch, clock.leaderScheduleEpoch, BigInt(unixTimestamp)));
+  };
+
+  const balance = async (address: PublicKey) => Number(await context.banksClient.getBalance(address));
+
+  const fund = (address: PublicKey) =>
+    context.setAccount(address, { lamports: 10 * LAMPORTS_PER_SOL, data: Buffer.alloc(0), owner: SystemProgram.programId, executable: false });
+
+  const betPDA = (market: PublicKey, bettor: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("bet"), market.toBuffer(), bettor.toBuffer()], program.programId)[0];
+
+  // creates a market closing in 100 seconds and places every bet in `bets`
+  const createMarket = async (question: string, options: { oracle?: object; optimistic?: object; kind?: object; committee?: object } = {}) => {
+    const factory = await program.account.marketFactory.fetch(factoryPDA);
+    const [market] = PublicKey.findProgramAddressSync([Buffer.from("market"), factory.marketCount.toArrayLike(Buffer, "le", 8)], program.programId);
+    const close = new BN((await now()) + 100);
+    await program.methods
+      .createMarket(question, ["Yes", "No"], close, close, close.addn(10_000), options.oracle ?? null, options.optimistic ?? null, null, defaultMetadata, null, options.kind ?? binary, noLimits, options.committee ?? null)
+      .accounts({ ...solCollateral, ...standalone, factory: factoryPDA, treasury: treasury.publicKey, categoryIndex: otherIndexPDA, creatorAllowance: null, market, authority: authority(), systemProgram: SystemProgram.programId })
+      .rpc();
+    for (const [bettor, outcome, amount] of bets) {
+      await program.methods
+        .placeBet(outcome, new BN(amount), [])
+        .accounts({ ...solBetAccounts, market, bettor: bettor.publicKey, bet: betPDA(market, bettor.publicKey), authority: authority(), systemProgram: SystemProgram.programId })
+        .signers([bettor])
+        .rpc();
+    }
+    return { market, eventTime: close.toNumber() };
+  };
+
+  // claims every bet that pays and returns the total paid out
+  const claimAll = async (market: PublicKey) => {
+    let paid = 0;
+    for (const [bettor] of bets) {
+      const before = await balance(bettor.publicKey);
+      try {
+        await program.methods
+          .claimPayout()
+          .accounts({ ...solBetAccounts, market, bet: betPDA(market, bettor.publicKey), bettor: bettor.publicKey, authority: authority() })
+          .signers([bettor])
+          .rpc();
+      } catch (err) {
+        assert.match(err.message, /NotWinningBet|NoPayout/);
+      }
+      paid += (await balance(bettor.publicKey)) - before;
+    }
+    return paid;
+  };
+
+  const assertConserved = async (market: PublicKey, paid: number) => {
+    const account = await context.banksClient.getAccount(market);
+    const rent = Number((await context.banksClient.getRent()).minimumBalance(BigInt(account.data.length)));
+    const dust = account.lamports - rent;
+    assert.isAtMost(paid, pool, "Payouts never exceed the pool");
+    assert.equal(paid + dust, pool, "Everything not paid out stays in the market");
+    assert.isBelow(dust, bets.length + 2, "Only rounding dust is left");
+  };
+
+  const expectError = async (promise: Promise<unknown>, error: string) => {
+    try {
+      await promise;
+      assert.fail(`Expected ${error}`);
+    } catch (err) {
+      assert.include(err.message, error);
+    }
+  };
+
+  before(async () => {
+    context = await startAnchor("", [], []);
+    provider = new BankrunProvider(context);
+    program = new Program<PredictionMarket>(IDL, provider);
+    for (const keypair of [alice, bob, carol, disputer]) {
+      fund(keypair.publicKey);
+    }
+
+    await program.methods
+      .initializeFactory(new BN(0), false, new BN(claimWindow))
+      .accounts({ factory: factoryPDA, admin: authority(), treasury: treasury.publicKey, systemProgram: SystemProgram.programId })
+      .rpc();
+    await program.methods
+      .initCategoryIndex({ other: {} })
+      .accounts({ factory: factoryPDA, admin: authority(), categoryIndex: otherIndexPDA, systemProgram: SystemProgram.programId })
+      .rpc();
+  });
+
+  it("Authority resolution", async () => {
+    const { market, eventTime } = await createMarket("Will the authority resolve it?");
+    const resolve = () => program.methods.resolveMarket(1).accounts({ ...standalone, market, authority: authority() }).rpc();
+
+    await expectError(resolve(), "MarketNotReslvable");
+    await warpTo(eventTime);
+    await resolve();
+
+    const paid = await claimAll(market);
+    assert.equal(paid, pool, "Carol takes the whole pool");
+    await assertConserved(market, paid);
+  });
+
+  it("Oracle resolution", async () => {
+    const feed = Keypair.generate().publicKey;
+    const oracle = { feed, comparison: { greaterThan: {} }, threshold: new BN(200_00000000), expo: -8, maxStaleness: new BN(60), maxConfidenceBps: 100 };
+    const { market, eventTime } = await createMarket("Will SOL close above $200?", { oracle });
+
+    // PriceFeed layout: magic, expo, price, conf, publish_time
+    const publish = (publishTime: number) => {
+      const data = Buffer.alloc(32);
+      data.writeUInt32LE(0xa1b2c3d4, 0);
+      data.writeInt32LE(-8, 4);
+      data.writeBigInt64LE(210_00000000n, 8);
+      data.writeBigUInt64LE(0n, 16);
+      data.writeBigInt64LE(BigInt(publishTime), 24);
+      context.setAccount(feed, { lamports: LAMPORTS_PER_SOL, data, owner: SystemProgram.programId, executable: false });
+    };
+    const resolve = () => program.methods.resolveWithOracle().accounts({ ...standalone, market, priceFeed: feed }).rpc();
+
+    await warpTo(eventTime + 120);
+    publish(eventTime);
+    await expectError(resolve(), "StalePrice");
+    publish(eventTime + 100);
+    await resolve();
+
+    const resolved = await program.account.market.fetch(market);
+    assert.equal(resolved.winningOutcome, 0);
+    await assertConserved(market, await claimAll(market));
+  });
+
+  it("Optimistic resolution after the challenge window, finalized once", async () => {
+    const bond = 1_000_000;
+    const optimistic = { arbiter: authority(), bond: new BN(bond), challengePeriod: new BN(3_600), arbitrationPeriod: new BN(86_400) };
+    const { market, eventTime } = await createMarket("Will nobody challenge the proposal?", { optimistic });
+    const finalize = () => program.methods.finalizeOutcome().accounts({ market, proposer: alice.publicKey }).rpc();
+
+    await warpTo(eventTime);
+    await program.methods.proposeOutcome(0).accounts({ ...standalone, market, proposer: alice.publicKey, systemProgram: SystemProgram.programId }).signers([alice]).rpc();
+    await expectError(finalize(), "ChallengeWindowOpen");
+
+    await warpTo(eventTime + 3_600);
+    const before = await balance(alice.publicKey);
+    await finalize();
+    assert.equal((await balance(alice.publicKey)) - before, bond, "The bond goes back to the proposer");
+
+    // replaying the finalization must not pay the bond out of the pool again
+    await warpTo(eventTime + 3_601);
+    await expectError(finalize(), "MarketNotActive");
+    await assertConserved(market, await claimAll(market));
+  });
+
+  it("Optimistic resolution through the arbiter", async () => {
+    const bond = 1_000_000;
+    const optimistic = { arbiter: authority(), bond: new BN(bond), challengePeriod: new BN(3_600), arbitrationPeriod: new BN(86_400) };
+    const { market, eventTime } = await createMarket("Will the arbiter side with the disputer?", { optimistic });
+
+    await warpTo(eventTime);
+    await program.methods.proposeOutcome(0).accounts({ ...standalone, market, proposer: alice.publicKey, systemProgram: SystemProgram.programId }).signers([alice]).rpc();
+    await program.methods.disputeOutcome().accounts({ market, disputer: disputer.publicKey, systemProgram: SystemProgram.programId }).signers([disputer]).rpc();
+
+    await warpTo(eventTime + 7_200);
+    const before = await balance(disputer.publicKey);
+    await program.methods.arbitrate(1).accounts({ market, arbiter: authority(), proposer: alice.publicKey, disputer: disputer.publicKey }).rpc();
+    assert.equal((await balance(disputer.publicKey)) - before, 2 * bond, "The winning side takes both bonds");
+
+    const paid = await claimAll(market);
+    assert.equal(paid, pool);
+    await assertConserved(market, paid);
+  });
+
+  it("Disputes the arbiter never rules on fall back to refunds", async () => {
+    const bond = 1_000_000;
+    const optimistic = { arbiter: authority(), bond: new BN(bond), challengePeriod: new BN(3_600), arbitrationPeriod: new BN(86_400) };
+    const { market, eventTime } = await createMarket("Will the arbiter go missing?", { optimistic });
+    const arbiterAccounts = { market, proposer: alice.publicKey, disputer: disputer.publicKey };
+
+    await warpTo(eventTime);
+    await program.methods.proposeOutcome(0).accounts({ ...standalone, market, proposer: alice.publicKey, systemProgram: SystemProgram.programId }).signers([alice]).rpc();
+    await program.methods.disputeOutcome().accounts({ market, disputer: disputer.publicKey, systemProgram: SystemProgram.programId }).signers([disputer]).rpc();
+    await expectError(program.methods.expireDispute().accounts(arbiterAccounts).rpc(), "ArbitrationWindowOpen");
+
+    await warpTo(eventTime + 3_600 + 86_400);
+    await expectError(program.methods.arbitrate(0).accounts({ ...arbiterAccounts, arbiter: authority() }).rpc(), "ArbitrationClosed");
+    const before = await balance(disputer.publicKey);
+    await program.methods.expireDispute().accounts(arbiterAccounts).rpc();
+    assert.equal((await balance(disputer.publicKey)) - before, bond);
+
+    for (const [bettor] of bets) {
+      await program.methods
+        .claimRefund()
+        .accounts({ ...solBetAccounts, market, bet: betPDA(market, bettor.publicKey), bettor: bettor.publicKey })
+        .signers([bettor])
+        .rpc();
+    }
+    await assertConserved(market, pool);
+  });
+
+  it("Markets settled after their claim deadline still get a full claim window", async () => {
+    const bond = 1_000_000;
+    // the challenge window alone runs past resolution_deadline + claim_window
+    const challengePeriod = 2 * claimWindow;
+    const optimistic = { arbiter: authority(), bond: new BN(bond), challengePeriod: new BN(challengePeriod), arbitrationPeriod: new BN(86_400) };
+    const { market, eventTime } = await createMarket("Will the proposal settle late?", { optimistic });
+
+    await warpTo(eventTime);
+    await program.methods.proposeOutcome(0).accounts({ ...standalone, market, proposer: alice.publicKey, systemProgram: SystemProgram.programId }).signers([alice]).rpc();
+    const settledAt = eventTime + challengePeriod;
+    await warpTo(settledAt);
+    assert.isAbove(settledAt, (await program.account.market.fetch(market)).claimDeadline.toNumber());
+    await program.methods.finalizeOutcome().accounts({ market, proposer: alice.publicKey }).rpc();
+
+    const resolved = await program.account.market.fetch(market);
+    assert.equal(resolved.claimDeadline.toNumber(), settledAt + claimWindow);
+    const finalize = () =>
+      program.methods
+        .finalizeMarket()
+        .accounts({ ...solCollateral, market, authority: authority(), factory: factoryPDA, treasury: treasury.publicKey, treasuryToken: null, authorityToken: null })
+        .rpc();
+    await expectError(finalize(), "ClaimPeriodOpen");
+
+    const paid = await claimAll(market);
+    assert.isAbove(paid, 3_000_001, "Alice and bob can still claim");
+    await assertConserved(market, paid);
+  });
+
+  it("Committee resolution", async () => {
+    const committee = { members: [alice.publicKey, bob.publicKey, carol.publicKey], threshold: 2 };
+    const { market, eventTime } = await createMarket("Will two of three members agree?", { committee });
+    const vote = (member: Keypair, outcome: number) =>
+      program.methods.voteResolution(outcome).accounts({ ...standalone, market, member: member.publicKey }).signers([member]).rpc();
+
+    await warpTo(eventTime);
+    await vote(alice, 0);
+    await vote(carol, 1);
+    await vote(bob, 0);
+
+    const paid = await claimAll(market);
+    assert.isAbove(paid, 3_000_001, "Alice and bob split the pool");
+    await assertConserved(market, paid);
+  });
+
+  it("Scalar resolution", async () => {
+    const kind = { scalar: { min: new BN(150), max: new BN(300) } };
+    const { market, eventTime } = await createMarket("Where will the index print?", { kind });
+
+    await warpTo(eventTime);
+    await program.methods.resolveScalar(new BN(260)).accounts({ ...standalone, market, authority: authority() }).rpc();
+
+    // LONG and SHORT both get a share of the pool
+    await assertConserved(market, await claimAll(market));
+  });
+
+  it("Finalizing returns a refundable market's exit fees to the creator and sweeps the rest", async () => {
+    const { market, eventTime } = await createMarket("Will the leftovers be swept?");
+    const deadline = eventTime + 10_000;
+
+    // right after creation the exit fee is the 1% floor
+    await program.methods
+      .withdrawBet()
+      .accounts({ ...solBetAccounts, market, bet: betPDA(market, alice.publicKey), bettor: alice.publicKey })
+      .signers([alice])
+      .rpc();
+    await warpTo(deadline);
+    await program.methods.markRefundable().accounts({ ...standalone, market }).rpc();
+    await program.methods
+      .claimRefund()
+      .accounts({ ...solBetAccounts, market, bet: betPDA(market, bob.publicKey), bettor: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const finalize = program.methods
+      .finalizeMarket()
+      .accounts({ ...solCollateral, market, authority: authority(), factory: factoryPDA, treasury: treasury.publicKey, treasuryToken: null, authorityToken: null });
+    await expectError(finalize.rpc(), "ClaimPeriodOpen");
+
+    await warpTo(deadline + claimWindow);
+    const account = await context.banksClient.getAccount(market);
+    const rent = Number((await context.banksClient.getRent()).minimumBalance(BigInt(account.data.length)));
+    const [treasuryBefore, creatorBefore] = [await balance(treasury.publicKey), await balance(authority())];
+
+    // a keeper cranks it, so the creator's balance only moves by what the market returns
+    const tx = await finalize.transaction();
+    tx.recentBlockhash = (await context.banksClient.getLatestBlockhash())[0];
+    tx.feePayer = disputer.publicKey;
+    tx.sign(disputer);
+    await context.banksClient.processTransaction(tx);
+
+    assert.equal((await balance(treasury.publicKey)) - treasuryBefore, 4_999_999, "Carol's unclaimed refund goes to the treasury");
+    assert.equal((await balance(authority())) - creatorBefore, 10_000 + rent, "The exit fee and the rent go back to the creator");
+    assert.isNull(await context.banksClient.getAccount(market), "Market account should be closed");
+  });
+
+  it("Markets nobody resolves become refundable after the deadline", async () => {
+    const { market, eventTime } = await createMarket("Will anyone turn up to resolve it?");
+
+    await warpTo(eventTime + 9_999);
+    await expectError(program.methods.markRefundable().accounts({ ...standalone, market }).rpc(), "ResolutionDeadlineNotPassed");
+    await warpTo(eventTime + 10_000);
+    await program.methods.markRefundable().accounts({ ...standalone, market }).rpc();
+    await expectError(program.methods.resolveMarket(0).accounts({ ...standalone, market, authority: authority() }).rpc(), "MarketNotActive");
+
+    for (const [bettor] of bets) {
+      await program.methods
+        .claimRefund()
+        .accounts({ ...solBetAccounts, market, bet: betPDA(market, bettor.publicKey), bettor: bettor.publicKey })
+        .signers([bettor])
+        .rpc();
+    }
+    await assertConserved(market, pool);
+  });
+});
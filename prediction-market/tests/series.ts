@@ -0,0 +1,139 @@
+import { Program } from "@coral-xyz/anchor";
+import { PredictionMarket } from "../target/types/prediction_market";
+import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { BN } from "bn.js";
+import { startAnchor, BankrunProvider } from "anchor-bankrun";
+import { Clock, ProgramTestContext } from "solana-bankrun";
+import { assert } from "chai";
+
+const IDL = require("../target/idl/prediction_market.json");
+
+// Recurring markets: create_series stores the template, spawn_next_market cranks each round's
+// market out of it. Driven in an in-process bank so round boundaries can be warped to.
+describe("market series", () => {
+  let context: ProgramTestContext;
+  let provider: BankrunProvider;
+  let program: Program<PredictionMarket>;
+
+  const authority = () => provider.wallet.publicKey;
+  const defaultMetadata = { category: { other: {} }, tags: [], resolutionSource: "", uri: "", contentHash: Array(32).fill(0) };
+  const noLimits = { minBet: new BN(0), maxBetPerBettor: new BN(0), maxOutcomeShareBps: 0, allowlistRoot: null };
+
+  const programId = new PublicKey(IDL.address);
+  const [factoryPDA] = PublicKey.findProgramAddressSync([Buffer.from("factory")], programId);
+  const [otherIndexPDA] = PublicKey.findProgramAddressSync([Buffer.from("category_index"), Buffer.from([6])], programId);
+  const treasury = PublicKey.unique();
+  const claimWindow = 24 * 60 * 60;
+  const duration = 3_600;
+  const resolutionWindow = 600;
+
+  const now = async () => Number((await context.banksClient.getClock()).unixTimestamp);
+
+  // moves to a new slot too, so repeating an identical transaction isn't deduplicated
+  const warpTo = async (unixTimestamp: number) => {
+    const clock = await context.banksClient.getClock();
+    const slot = clock.slot + 1n;
+    context.warpToSlot(slot);
+    context.setClock(new Clock(slot, clock.epochStartTimestamp, clock.epoch, clock.leaderScheduleEpoch, BigInt(unixTimestamp)));
+  };
+
+  const expectError = async (promise: Promise<unknown>, error: string) => {
+    try {
+      await promise;
+      assert.fail(`Expected ${error}`);
+    } catch (err) {
+      assert.include(err.message, error);
+    }
+  };
+
+  const nextMarketPDA = async () => {
+    const factory = await program.account.marketFactory.fetch(factoryPDA);
+    return PublicKey.findProgramAddressSync([Buffer.from("market"), factory.marketCount.toArrayLike(Buffer, "le", 8)], programId)[0];
+  };
+
+  const spawn = async (series: PublicKey, previousMarket: PublicKey | null) => {
+    const market = await nextMarketPDA();
+    await program.methods
+      .spawnNextMarket()
+      .accounts({ series, market, previousMarket, payer: authority(), factory: factoryPDA, treasury, categoryIndex: otherIndexPDA, systemProgram: SystemProgram.programId })
+      .rpc();
+    return market;
+  };
+
+  before(async () => {
+    context = await startAnchor("", [], []);
+    provider = new BankrunProvider(context);
+    program = new Program<PredictionMarket>(IDL, provider);
+
+    await program.methods
+      .initializeFactory(new BN(0), false, new BN(claimWindow))
+      .accounts({ factory: factoryPDA, admin: authority(), treasury, systemProgram: SystemProgram.programId })
+      .rpc();
+    await program.methods
+      .initCategoryIndex({ other: {} })
+      .accounts({ factory: factoryPDA, admin: authority(), categoryIndex: otherIndexPDA, systemProgram: SystemProgram.programId })
+      .rpc();
+  });
+
+  it("Spawns each round from the template, linked to the previous one", async () => {
+    const name = "btc-hourly";
+    const [series] = PublicKey.findProgramAddressSync([Buffer.from("series"), authority().toBuffer(), Buffer.from(name)], programId);
+    const firstRoundAt = (await now()) + 100;
+
+    await program.methods
+      .createSeries(name, "BTC up or down in round {round}?", ["Up", "Down"], new BN(firstRoundAt), new BN(duration), new BN(resolutionWindow), null, defaultMetadata, noLimits)
+      .accounts({ series, authority: authority(), factory: factoryPDA, creatorAllowance: null, systemProgram: SystemProgram.programId })
+      .rpc();
+
+    await expectError(spawn(series, null), "RoundNotOpen");
+
+    await warpTo(firstRoundAt);
+    const first = await spawn(series, null);
+    const round0 = await program.account.market.fetch(first);
+    assert.equal(round0.question, "BTC up or down in round 0?");
+    assert.deepEqual(round0.outcomes, ["Up", "Down"]);
+    assert.isNull(round0.previousMarket);
+    assert.equal(round0.series.toBase58(), series.toBase58());
+    assert.equal(round0.bettingCloseTime.toNumber(), firstRoundAt + duration);
+    assert.equal(round0.resolutionDeadline.toNumber(), firstRoundAt + duration + resolutionWindow);
+    assert.equal(round0.claimDeadline.toNumber(), firstRoundAt + duration + resolutionWindow + claimWindow);
+
+    // the next round only opens when this one's betting closes
+    await warpTo(firstRoundAt + 1);
+    await expectError(spawn(series, first), "RoundNotOpen");
+
+    // a crank that turns up two and a half rounds late skips the rounds that already closed
+    const lateBy = 3 * duration + 50;
+    await warpTo(firstRoundAt + lateBy);
+    await expectError(spawn(series, null), "PreviousMarketMismatch");
+    await expectError(spawn(series, PublicKey.unique()), "PreviousMarketMismatch");
+    const next = await spawn(series, first);
+
+    const round3 = await program.account.market.fetch(next);
+    const opensAt = firstRoundAt + 3 * duration;
+    assert.equal(round3.question, "BTC up or down in round 3?");
+    assert.equal(round3.previousMarket.toBase58(), first.toBase58());
+    assert.equal(round3.bettingCloseTime.toNumber(), opensAt + duration);
+    assert.equal(round3.claimDeadline.toNumber(), opensAt + duration + resolutionWindow + claimWindow);
+
+    const state = await program.account.marketSeries.fetch(series);
+    assert.equal(state.nextRound.toNumber(), 4);
+    assert.equal(state.nextRoundAt.toNumber(), opensAt + duration);
+    assert.equal(state.lastMarket.toBase58(), next.toBase58());
+  });
+
+  it("Rejects question formats that could expand past the market's limit", async () => {
+    const name = "too-long";
+    const [series] = PublicKey.findProgramAddressSync([Buffer.from("series"), authority().toBuffer(), Buffer.from(name)], programId);
+    // 170 bytes, but at round u64::MAX each placeholder grows by 13
+    const format = "x".repeat(170 - 3 * 7) + "{round}{round}{round}";
+
+    await expectError(
+      program.methods
+        .createSeries(name, format, ["Up", "Down"], new BN(await now()), new BN(duration), new BN(resolutionWindow), null, defaultMetadata, noLimits)
+        .accounts({ series, authority: authority(), factory: factoryPDA, creatorAllowance: null, systemProgram: SystemProgram.programId })
+        .rpc(),
+      "QuestionTooLong"
+    );
+  });
+});